@@ -0,0 +1,50 @@
+use std::{env, process::ExitCode};
+
+use descend::{book::{Book, BOOK_PLIES}, game::load_records};
+
+/// Builds an opening book from a directory of game records.
+///
+/// Usage: `build_book <records dir> <book file> [plies]`
+fn main() -> ExitCode {
+    let args: Vec<String> = env::args().collect();
+    if args.len() < 3 || args.len() > 4 {
+        eprintln!("usage: {} <records dir> <book file> [plies]", args[0]);
+        return ExitCode::FAILURE;
+    }
+    let plies = match args.get(3).map(|p| p.parse::<usize>()) {
+        None => BOOK_PLIES,
+        Some(Ok(plies)) => plies,
+        Some(Err(_)) => {
+            eprintln!("plies must be a number");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let records = match load_records(&args[1]) {
+        Ok(records) => records,
+        Err(e) => {
+            eprintln!("could not read {}: {e}", args[1]);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let mut book = Book::default();
+    let mut games = 0;
+    for (path, game) in records {
+        match game {
+            Ok(game) => if book.add_game(&game, plies) {
+                games += 1;
+            } else {
+                eprintln!("skipping {}: game is not finished", path.display());
+            },
+            Err(e) => eprintln!("skipping {}: {e:?}", path.display())
+        }
+    }
+
+    if let Err(e) = book.save(&args[2]) {
+        eprintln!("could not write {}: {e}", args[2]);
+        return ExitCode::FAILURE;
+    }
+    println!("added {games} games, {} positions", book.len());
+    return ExitCode::SUCCESS;
+}
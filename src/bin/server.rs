@@ -0,0 +1,39 @@
+use std::{env, net::TcpListener, process::ExitCode, sync::{Arc, Mutex}, thread, time::Duration};
+
+use descend::server::{
+    serve, serve_websocket, Server,
+    DEFAULT_FORFEIT_TIMEOUT, DEFAULT_SERVER_ADDRESS, DEFAULT_WEBSOCKET_ADDRESS
+};
+
+/// Runs the game server without a window, over TCP and WebSocket.
+///
+/// Usage: `server [address] [forfeit timeout in seconds] [websocket address]`
+fn main() -> ExitCode {
+    let args: Vec<String> = env::args().collect();
+    let address = args.get(1).map_or(DEFAULT_SERVER_ADDRESS, String::as_str);
+    let websocket_address = args.get(3).map_or(DEFAULT_WEBSOCKET_ADDRESS, String::as_str);
+    let forfeit_timeout = match args.get(2).map(|secs| secs.parse()) {
+        None => DEFAULT_FORFEIT_TIMEOUT,
+        Some(Ok(secs)) => Duration::from_secs(secs),
+        Some(Err(_)) => {
+            eprintln!("usage: {} [address] [forfeit timeout in seconds] [websocket address]", args[0]);
+            return ExitCode::FAILURE;
+        }
+    };
+    let listeners = TcpListener::bind(address)
+        .and_then(|listener| Ok((listener, TcpListener::bind(websocket_address)?)));
+    let (listener, websocket_listener) = match listeners {
+        Ok(listeners) => listeners,
+        Err(e) => {
+            eprintln!("could not listen on {address} and {websocket_address}: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    println!("listening on {address}, and for WebSocket clients on {websocket_address}");
+    let server = Arc::new(Mutex::new(Server::new(forfeit_timeout)));
+    let websocket_server = server.clone();
+    thread::spawn(move || serve_websocket(websocket_server, websocket_listener));
+    serve(server, listener);
+    return ExitCode::SUCCESS;
+}
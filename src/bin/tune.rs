@@ -0,0 +1,66 @@
+use std::{env, process::ExitCode};
+
+use descend::{eval::Evaluator, game::load_records, tune::{fit_scale, get_error, get_samples, tune}};
+
+const ITERATIONS: usize = 1000;
+
+/// Fits evaluation weights to the results of a directory of game records.
+///
+/// Usage: `tune <records dir> <output weights> [start weights] [iterations]`
+fn main() -> ExitCode {
+    let args: Vec<String> = env::args().collect();
+    if args.len() < 3 || args.len() > 5 {
+        eprintln!("usage: {} <records dir> <output weights> [start weights] [iterations]", args[0]);
+        return ExitCode::FAILURE;
+    }
+    let start = match args.get(3) {
+        None => Evaluator::default(),
+        Some(path) => match Evaluator::load(path) {
+            Ok(evaluator) => evaluator,
+            Err(e) => {
+                eprintln!("could not read {path}: {e}");
+                return ExitCode::FAILURE;
+            }
+        }
+    };
+    let iterations = match args.get(4).map(|i| i.parse::<usize>()) {
+        None => ITERATIONS,
+        Some(Ok(iterations)) => iterations,
+        Some(Err(_)) => {
+            eprintln!("iterations must be a number");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let records = match load_records(&args[1]) {
+        Ok(records) => records,
+        Err(e) => {
+            eprintln!("could not read {}: {e}", args[1]);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let mut samples = Vec::new();
+    for (path, game) in records {
+        match game {
+            Ok(game) => samples.extend(get_samples(&game)),
+            Err(e) => eprintln!("skipping {}: {e:?}", path.display())
+        }
+    }
+    if samples.is_empty() {
+        eprintln!("no positions from finished games in {}", args[1]);
+        return ExitCode::FAILURE;
+    }
+
+    let k = fit_scale(&start.weights, &samples);
+    println!("{} positions, scale {k}, starting error {}", samples.len(), get_error(&start.weights, &samples, k));
+    let tuned = tune(&start, &samples, iterations, |iteration, error| {
+        println!("iteration {iteration}: error {error}");
+    });
+
+    if let Err(e) = tuned.save(&args[2]) {
+        eprintln!("could not write {}: {e}", args[2]);
+        return ExitCode::FAILURE;
+    }
+    return ExitCode::SUCCESS;
+}
@@ -1,7 +1,6 @@
-use bevy::{asset::{Assets, Handle}, ecs::{component::Component, query::Without, system::{Query, Res, ResMut, Resource}}, math::primitives::{Circle, Rectangle}, render::mesh::Mesh, sprite::{ColorMaterial, Mesh2dHandle}, text::Text};
-use bevy::render::color::Color as BevyColor;
+use bevy::{prelude::*, sprite::{MaterialMesh2dBundle, Mesh2dHandle}};
 
-use crate::{tile::{Color, Face, Selected, Tile}, InventoryUI, LineUI, BLOCK_SIZE, CARD_SIZE, MAX_SIZE, SIZE_X, SIZE_Y};
+use crate::{game::{Game, Move}, tile::{Color, Face, Selected, Tile}, InventoryUI, Layout, LineUI, Theme};
 
 #[derive(Component, Clone)]
 pub struct Board {
@@ -21,13 +20,24 @@ pub struct Inventory {
 #[derive(Component)]
 pub struct Index(pub usize);
 
+/// A run of two or more cards of one color, counting the Wild for both colors.
+/// `dir` is the step from one card of the line to the next.
+#[derive(Clone, Copy, Debug)]
+pub struct Line {
+    pub color: Color,
+    pub start: (u32, u32),
+    pub dir: (i32, i32),
+    pub len: u32
+}
+
 #[derive(Clone, Copy)]
 pub enum Dir {
     Left, Up, Right, Down
 }
 
+/// Black's then Red's number of lines of each length, starting from one card.
 #[derive(Resource)]
-pub struct LineCount(pub [[usize; MAX_SIZE as usize]; 2]);
+pub struct LineCount(pub [Vec<usize>; 2]);
 
 impl Board {
     pub fn new(x: u32, y: u32) -> Board {
@@ -41,7 +51,7 @@ impl Board {
     }
     
     pub fn place_tile(&mut self, x: u32, y: u32, face: Face) -> Result<(), TileError> {
-        self.can_place_tile(x, y, face).or(self.can_place_free(x, y, face))?;
+        self.check_tile(x, y, face)?;
         *self.get_mut_tile(x, y)? = Tile::Card(face, self.color);
         
         self.clear_blocked_tiles();
@@ -108,10 +118,10 @@ impl Board {
     }
 
     pub fn can_place_free(&self, x: u32, y: u32, face: Face) -> Result<(), TileError> {
-        if self.any_legal_move(face) {
-            return Err(TileError::NotFree);
-        } else if self.get_tile(x, y)? != Tile::None {
+        if self.get_tile(x, y)? != Tile::None {
             return Err(TileError::BlockedTile);
+        } else if self.any_legal_move(face) {
+            return Err(TileError::NotFree);
         }
 
         let surrounding_tiles: Vec<Option<Tile>> = [
@@ -129,6 +139,13 @@ impl Board {
 
     }
 
+    /// Whether `face` can go on the square at `x`, `y`: against a higher card, or next to
+    /// any card once it is free.  A square that is taken or blocked is `BlockedTile`, a face
+    /// that still fits against a higher card somewhere is `NotFree`.
+    pub fn check_tile(&self, x: u32, y: u32, face: Face) -> Result<(), TileError> {
+        return self.can_place_tile(x, y, face).or(self.can_place_free(x, y, face));
+    }
+
     pub fn any_legal_move(&self, face: Face) -> bool {
         for i in 0..self.board.len() as u32 {
             let x = i % self.size.0;
@@ -140,6 +157,65 @@ impl Board {
         return false;
     }
 
+    /// Every placement the player to move can make with the faces left in `inv`.
+    pub fn get_legal_moves(&self, inv: &Inventory) -> Vec<Move> {
+        let mut moves = Vec::new();
+        for face in [Face::Ace, Face::King, Face::Queen, Face::Jack] {
+            if !inv.can_place_face(face) {
+                continue;
+            }
+            let free = !self.any_legal_move(face);
+            for i in 0..self.board.len() as u32 {
+                let x = i % self.size.0;
+                let y = i / self.size.0;
+                let legal = if free {
+                    self.can_place_free(x, y, face).is_ok()
+                } else {
+                    self.can_place_tile(x, y, face).is_ok()
+                };
+                if legal {
+                    moves.push(Move {x, y, face});
+                }
+            }
+        }
+        return moves;
+    }
+
+    /// Finds every line of both players, running left to right, bottom to top and along both diagonals.
+    pub fn get_line_segments(&self) -> Vec<Line> {
+        let owns = |color: Color, x: i32, y: i32| -> bool {
+            if x < 0 || y < 0 {
+                return false;
+            }
+            match self.get_tile(x as u32, y as u32) {
+                Ok(Tile::Card(_, c)) => c == color || c == Color::Both,
+                _ => false
+            }
+        };
+
+        let mut lines = Vec::new();
+        for color in [Color::Red, Color::Black] {
+            for dir in [(1, 0), (0, 1), (1, 1), (1, -1)] {
+                for i in 0..self.board.len() as u32 {
+                    let x = (i % self.size.0) as i32;
+                    let y = (i / self.size.0) as i32;
+                    if !owns(color, x, y) || owns(color, x - dir.0, y - dir.1) {
+                        continue;
+                    }
+
+                    let mut len = 1;
+                    while owns(color, x + dir.0 * len, y + dir.1 * len) {
+                        len += 1;
+                    }
+                    if len > 1 {
+                        lines.push(Line {color, start: (x as u32, y as u32), dir, len: len as u32});
+                    }
+                }
+            }
+        }
+        return lines;
+    }
+
     pub fn get_tile(&self, x: u32, y: u32) -> Result<Tile, TileError> {
         if x >= self.size.0 {
             return Err(TileError::XOutOfBounds);
@@ -162,6 +238,53 @@ impl Board {
         return Ok(&mut self.board[index as usize]);
     }
 
+    pub fn get_size(&self) -> (u32, u32) {
+        return self.size;
+    }
+
+    /// A hash of everything that decides the rest of the game: the tiles, the
+    /// player to move and both inventories.  Unlike `std::hash` it is stable
+    /// between runs, so it can be written to files such as the opening book.
+    pub fn position_hash(&self, red_inv: &Inventory, black_inv: &Inventory) -> u64 {
+        const FNV_OFFSET: u64 = 0xcbf29ce484222325;
+        const FNV_PRIME: u64 = 0x100000001b3;
+
+        let mut bytes = vec![self.size.0 as u8, self.size.1 as u8, self.color as u8];
+        bytes.extend(self.board.iter().map(|tile| match tile {
+            Tile::None => 0,
+            Tile::Blocked => 1,
+            Tile::Card(face, color) => 2 + *face as u8 * 3 + *color as u8
+        }));
+        for inv in [red_inv, black_inv] {
+            bytes.extend([inv.aces, inv.kings, inv.queens, inv.jacks].map(|n| n as u8));
+        }
+
+        return bytes.into_iter().fold(FNV_OFFSET, |hash, byte| (hash ^ byte as u64).wrapping_mul(FNV_PRIME));
+    }
+
+    /// Compares both players' lines from the longest down, following the tie-break rule.
+    /// Returns `None` if the game is a draw.
+    pub fn get_winner(&self) -> Option<Color> {
+        let lines = self.get_lines();
+        for len in (1..lines[0].len()).rev() {
+            if lines[1][len] > lines[0][len] {
+                return Some(Color::Red);
+            } else if lines[0][len] > lines[1][len] {
+                return Some(Color::Black);
+            }
+        }
+        return None;
+    }
+
+    /// Black's then Red's line lengths, longest first, the way the tie-break compares them.
+    pub fn get_line_lengths(&self) -> [Vec<usize>; 2] {
+        return self.get_lines().map(|counts| {
+            counts.iter().enumerate().skip(1).rev()
+                .flat_map(|(i, count)| std::iter::repeat(i + 1).take(*count))
+                .collect()
+        });
+    }
+
     pub fn set_tile(&mut self, x: u32, y: u32, tile: Tile) {
         if x >= self.size.0 {
             panic!();
@@ -178,16 +301,15 @@ impl Board {
     const POS_DIAGONAL_BITMASK: u8 = 0b0010;
     const HORIZONTAL_BITMASK: u8 = 0b0001;
 
-    const RIGHT_OFFSET: usize = 1;
-    const DOWN_LEFT_OFFSET: usize = SIZE_X as usize - 1;
-    const DOWN_OFFSET: usize = SIZE_X as usize;
-    const DOWN_RIGHT_OFFSET: usize = SIZE_X as usize + 1;
-
-    const WILD_INDEX: usize = (SIZE_X * SIZE_Y / 2) as usize;
+    fn wild_index(&self) -> usize {
+        return self.board.len() / 2;
+    }
 
-    pub fn get_lines(&self) -> [[usize; MAX_SIZE as usize]; 2] {
-        let mut flags: [u8; (SIZE_X * SIZE_Y) as usize] = [0; (SIZE_X * SIZE_Y) as usize];
-        let mut counts: [[usize; MAX_SIZE as usize]; 2] = [[0; MAX_SIZE as usize]; 2];
+    /// Counts the lines of each length for Black then Red, up to the longer side of the board.
+    pub fn get_lines(&self) -> [Vec<usize>; 2] {
+        let mut flags = vec![0; self.board.len()];
+        let max_len = self.size.0.max(self.size.1) as usize;
+        let mut counts = [vec![0; max_len], vec![0; max_len]];
 
         //eprintln!("starting");
         for i in 0..flags.len() {
@@ -208,60 +330,61 @@ impl Board {
         return counts;
     }
 
-    fn on_left_edge(index: usize) -> bool {
-        return index % SIZE_X as usize == 0;
+    fn on_left_edge(&self, index: usize) -> bool {
+        return index % self.size.0 as usize == 0;
     }
 
-    fn on_right_edge(index: usize) -> bool {
-        return index % SIZE_X as usize == SIZE_X as usize - 1; 
+    fn on_right_edge(&self, index: usize) -> bool {
+        return index % self.size.0 as usize == self.size.0 as usize - 1; 
     }
 
-    fn on_bottom_edge(index: usize) -> bool {
-        return index / SIZE_Y as usize == SIZE_Y as usize - 1;
+    fn on_bottom_edge(&self, index: usize) -> bool {
+        return index / self.size.0 as usize == self.size.1 as usize - 1;
     }
 
     fn handle_lines(&self,
-        flags: &mut [u8; (SIZE_X * SIZE_Y) as usize], counts: &mut [[usize; MAX_SIZE as usize]; 2], 
+        flags: &mut [u8], counts: &mut [Vec<usize>; 2], 
         index:usize, color: Color
     ) {
-        let shift = if index == Self::WILD_INDEX && color == Color::Red {4} else {0};
+        let width = self.size.0 as usize;
+        let shift = if index == self.wild_index() && color == Color::Red {4} else {0};
         const MASK: u8 = 0b1111;
         if (flags[index] >> shift) & MASK & Self::NEG_DIAGONAL_BITMASK == 0 {
             self.handle_line(
                 flags, counts, color, 
-                Self::NEG_DIAGONAL_BITMASK, Self::DOWN_RIGHT_OFFSET, index, 
-                |index| Self::on_right_edge(index) || Self::on_bottom_edge(index)
+                Self::NEG_DIAGONAL_BITMASK, width + 1, index, 
+                |index| self.on_right_edge(index) || self.on_bottom_edge(index)
             );
         }
 
         if (flags[index] >> shift) & MASK & Self::VERTICAL_BITMASK == 0 {
             self.handle_line(
                 flags, counts, color, 
-                Self::VERTICAL_BITMASK, Self::DOWN_OFFSET, index, 
-                |index| Self::on_bottom_edge(index)
+                Self::VERTICAL_BITMASK, width, index, 
+                |index| self.on_bottom_edge(index)
             );
         }
 
         if (flags[index] >> shift) & MASK & Self::POS_DIAGONAL_BITMASK == 0 {
             self.handle_line(
                 flags, counts, color, 
-                Self::POS_DIAGONAL_BITMASK, Self::DOWN_LEFT_OFFSET, index, 
-                |index| Self::on_left_edge(index) || Self::on_bottom_edge(index)
+                Self::POS_DIAGONAL_BITMASK, width - 1, index, 
+                |index| self.on_left_edge(index) || self.on_bottom_edge(index)
             );
         }
 
         if (flags[index] >> shift) & MASK & Self::HORIZONTAL_BITMASK == 0 {
             self.handle_line(
                 flags, counts, color, 
-                Self::HORIZONTAL_BITMASK, Self::RIGHT_OFFSET, index, 
-                |index| Self::on_right_edge(index)
+                Self::HORIZONTAL_BITMASK, 1, index, 
+                |index| self.on_right_edge(index)
             );
         }
     }
 
     fn handle_line(&self,
-        flags: &mut [u8; (SIZE_X * SIZE_Y) as usize], counts: &mut [[usize; MAX_SIZE as usize]; 2], 
-        color: Color, bitmask: u8, offset: usize, index: usize, index_check: fn(usize) -> bool
+        flags: &mut [u8], counts: &mut [Vec<usize>; 2], 
+        color: Color, bitmask: u8, offset: usize, index: usize, index_check: impl Fn(usize) -> bool
     ) {
         //eprintln!("bitmask: {}\noffset: {}", bitmask, offset);
         let mut i = index;
@@ -277,7 +400,7 @@ impl Board {
                 break;
             }
             count += 1;
-            if i == Self::WILD_INDEX {
+            if i == self.wild_index() {
                 let shift = if color == Color::Red {4} else {0}; 
                 flags[i] |= bitmask << shift;
             } else {
@@ -304,6 +427,10 @@ impl Inventory {
         }
     }
 
+    pub fn from_counts(aces: u32, kings: u32, queens: u32, jacks: u32) -> Inventory {
+        Inventory {aces, kings, queens, jacks}
+    }
+
     pub fn is_empty(&self) -> bool {
         return self.aces == 0 && self.kings == 0 && self.queens == 0 && self.jacks == 0;
     }
@@ -401,47 +528,56 @@ pub fn update_screen(
     mut face_meshes: Query<(&mut Mesh2dHandle, &mut Handle<ColorMaterial>), Without<Index>>,
     mut inventory_text: Query<(&mut Text, &Color, &Face), (Without<LineUI>, Without<Index>, Without<InventoryUI>)>,
     line_counts: Res<LineCount>,
+    layout: Res<Layout>,
+    theme: Res<Theme>,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<ColorMaterial>>,
 ) {
     let b = board.single();
     let s = selected.single().face;
-    for ((mesh, color, Index(i)), (mut text, _)) in board_meshes.iter_mut().zip(board_text.iter_mut()) {
-        let x = *i as u32 % SIZE_X;
-        let y = *i as u32 / SIZE_X;
-
-        meshes.insert(&mesh.0, match b.get_tile(x, y).ok().unwrap() {
-            Tile::None => Into::<Mesh>::into(Rectangle::new(CARD_SIZE, CARD_SIZE)),
-            Tile::Blocked => Circle::new(BLOCK_SIZE).into(),
-            Tile::Card(..) => Rectangle::new(CARD_SIZE, CARD_SIZE).into()
+    for (mesh, color, Index(i)) in &mut board_meshes {
+        let x = *i as u32 % b.get_size().0;
+        let y = *i as u32 / b.get_size().0;
+        // squares of another board size are about to be replaced
+        let Ok(tile) = b.get_tile(x, y) else {
+            continue;
+        };
+
+        meshes.insert(&mesh.0, match tile {
+            Tile::None => Into::<Mesh>::into(Rectangle::new(layout.card_size, layout.card_size)),
+            Tile::Blocked => Circle::new(layout.block_size).into(),
+            Tile::Card(..) => Rectangle::new(layout.card_size, layout.card_size).into()
         });
-        materials.insert(color.id(), match b.get_tile(x, y).ok().unwrap() {
+        materials.insert(color.id(), match tile {
             Tile::None => if let Some(face) = s {
-                if b.can_place_tile(x, y, face).or(b.can_place_free(x, y, face)).is_ok() {
-                    BevyColor::rgba(1., 1., 1., 1.)
+                if b.check_tile(x, y, face).is_ok() {
+                    theme.playable
                 } else {
-                    BevyColor::rgba(0., 0., 0., 1.)
+                    theme.empty
                 }
             } else {
-                BevyColor::rgba(0., 0., 0., 1.)
+                theme.empty
             },
-            Tile::Blocked => BevyColor::rgba(1., 1., 0., 1.),
-            Tile::Card(_, Color::Black) => BevyColor::rgba(0.5, 0.5, 0.5, 1.),
-            Tile::Card(_, Color::Red) => BevyColor::rgba(1., 0., 0., 1.),
-            Tile::Card(_, Color::Both) => BevyColor::rgba(1., 0., 1., 1.)
+            Tile::Blocked => theme.blocked,
+            Tile::Card(_, color) => theme.color(color)
         }.into());
-        text.sections[0].value = match b.get_tile(x, y).ok().unwrap() {
-            Tile::Card(Face::Wild, ..) => "W",
-            Tile::Card(Face::Ace, ..) => "A",
-            Tile::Card(Face::King, ..) => "K",
-            Tile::Card(Face::Queen, ..) => "Q",
-            Tile::Card(Face::Jack, ..) => "J",
+    }
+    for (mut text, Index(i)) in &mut board_text {
+        let x = *i as u32 % b.get_size().0;
+        let y = *i as u32 / b.get_size().0;
+        text.sections[0].value = match b.get_tile(x, y) {
+            Ok(Tile::Card(Face::Wild, ..)) => "W",
+            Ok(Tile::Card(Face::Ace, ..)) => "A",
+            Ok(Tile::Card(Face::King, ..)) => "K",
+            Ok(Tile::Card(Face::Queen, ..)) => "Q",
+            Ok(Tile::Card(Face::Jack, ..)) => "J",
             _ => " "
         }.to_string();
     }
 
     for (mut text, size, color) in &mut line_text {
-        text.sections[0].value = format!("{}: {}", size.0 + 1, line_counts.0[(*color == Color::Red) as usize][size.0]);
+        let count = line_counts.0[(*color == Color::Red) as usize].get(size.0).copied().unwrap_or(0);
+        text.sections[0].value = format!("{}: {count}", size.0 + 1);
     }
 
     let bc = b.color;
@@ -454,15 +590,11 @@ pub fn update_screen(
             } else {
                 meshes.insert(&mesh.0, Rectangle::new(75., 75.).into());
             }
-            materials.insert(color.id(), match bc {
-                Color::Red => BevyColor::rgba(1., 0., 0., 1.),
-                Color::Black => BevyColor::rgba(0.5, 0.5, 0.5, 1.),
-                Color::Both => BevyColor::rgba(1., 0., 1., 1.)
-            }.into());
+            materials.insert(color.id(), theme.color(bc).into());
             continue;
         } 
         meshes.insert(&mesh.0, Rectangle::new(75., 75.).into());
-        materials.insert(color.id(), BevyColor::rgba(0.2, 0.2, 0.2, 1.).into());
+        materials.insert(color.id(), theme.used_up.into());
     }
 
     for (mut text, color, face) in inventory_text.iter_mut() {
@@ -473,12 +605,106 @@ pub fn update_screen(
             Face::Queen => "Q",
             Face::Jack => "J",
             _ => panic!()
-        }, match inv.get_num_face(*face) {
-            0 => "     ",
-            1 => "*    ",
-            2 => "* *  ",
-            3 => "* * *",
-            _ => panic!()
-        })
+        }, format!("{:<5}", vec!["*"; inv.get_num_face(*face) as usize].join(" ")))
     }
-}
\ No newline at end of file
+}
+
+/// Spawns the squares of `board` and the line counts for its size, and lays the screen out for it.
+pub fn spawn_board(
+    commands: &mut Commands,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<ColorMaterial>,
+    theme: &Theme,
+    board: &Board
+) {
+    let layout = Layout::new(board.get_size());
+    let tile_text_style = TextStyle {
+        font: default(),
+        font_size: layout.font_size,
+        color: theme.card_text
+    };
+    let red_line_text_style = TextStyle {
+        font: default(),
+        font_size: 30.,
+        color: theme.red
+    };
+    let black_line_text_style = TextStyle {
+        font: default(),
+        font_size: 30.,
+        color: theme.black
+    };
+
+    let (size_x, size_y) = board.get_size();
+    for i in 0..(size_x * size_y) as usize {
+        let x = i as u32 % size_x;
+        let y = i as u32 / size_x;
+        let position = layout.square_position(x, y);
+
+        commands.spawn((MaterialMesh2dBundle {
+            mesh: Mesh2dHandle(meshes.add(Rectangle::new(layout.card_size, layout.card_size))),
+            material: materials.add(theme.empty),
+            transform: Transform::from_xyz(position.x, position.y, 0.),
+            ..default()
+        }, Index(i)));
+        commands.spawn((Text2dBundle {
+            text: Text::from_section(" ", tile_text_style.clone()),
+            transform: Transform::from_xyz(position.x, position.y, 1.),
+            ..default()
+        }, Index(i)));
+    }
+
+    for i in 1..size_x.max(size_y) as usize {
+        commands.spawn((TextBundle::from_section(
+            format!("{}: 0", i + 1), 
+            red_line_text_style.clone()
+        ).with_style(Style {
+            position_type: PositionType::Absolute,
+            top: Val::Px(10. + (i - 1) as f32 * 20.),
+            right: Val::Percent(55.),
+            ..Default::default()
+        }), LineUI(i), Color::Red, Interaction::default()));
+        commands.spawn((TextBundle::from_section(
+            format!("{}: 0", i + 1), 
+            black_line_text_style.clone()
+        ).with_style(Style {
+            position_type: PositionType::Absolute,
+            top: Val::Px(10. + (i - 1) as f32 * 20.),
+            left: Val::Percent(55.),
+            ..Default::default()
+        }), LineUI(i), Color::Black, Interaction::default()));
+    }
+    commands.insert_resource(layout);
+}
+
+/// Rebuilds the board on screen when the game changes to another board size,
+/// e.g. when joining a game hosted on a bigger board.
+pub fn resize_board_system(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    layout: Res<Layout>,
+    theme: Res<Theme>,
+    board: Query<&Board>,
+    squares: Query<Entity, Or<(With<Index>, With<LineUI>)>>,
+) {
+    let b = board.single();
+    if b.get_size() == layout.size {
+        return;
+    }
+    for entity in &squares {
+        commands.entity(entity).despawn();
+    }
+    spawn_board(&mut commands, &mut meshes, &mut materials, &theme, b);
+}
+
+/// Spawns the game and the state of its current position: the board, both inventories,
+/// the selected face and the line counts.
+pub fn spawn_game(commands: &mut Commands, game: &Game) {
+    let (_, board, (red_inv, black_inv)) = game.get_move();
+    commands.insert_resource(LineCount(board.get_lines()));
+    commands.spawn(board.clone());
+    commands.spawn((black_inv.clone(), Color::Black));
+    commands.spawn((red_inv.clone(), Color::Red));
+    commands.spawn(game.clone());
+    commands.spawn(Selected {face: None});
+}
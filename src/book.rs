@@ -0,0 +1,175 @@
+use std::{collections::HashMap, fs, io, path::Path};
+
+use bevy::{ecs::{component::Component, query::With, system::{Query, Res, Resource}}, text::Text};
+
+use crate::{board::{Board, Inventory}, game::{Game, Move}, tile::Color};
+
+/// How many plies from the start of each game are added to the book by default.
+pub const BOOK_PLIES: usize = 10;
+
+#[derive(Clone)]
+pub struct BookMove {
+    pub mv: Move,
+    pub weight: u32,
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32
+}
+
+/// Opening book mapping a `Board::position_hash` to the moves played from that position.
+/// Wins, draws and losses are counted for the player making the move.
+///
+/// On disk every move is one line: `<hash> <move> <weight> <wins> <draws> <losses>`,
+/// with the hash written in hex and the move in `Move` notation.
+#[derive(Resource, Default)]
+pub struct Book {
+    positions: HashMap<u64, Vec<BookMove>>
+}
+
+#[derive(Component)]
+pub struct BookUI;
+
+impl Book {
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Book> {
+        let mut book = Book::default();
+        for line in fs::read_to_string(path)?.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (hash, book_move) = Self::parse_line(line).ok_or_else(|| io::Error::new(
+                io::ErrorKind::InvalidData, format!("invalid book line: {line}")
+            ))?;
+            book.positions.entry(hash).or_default().push(book_move);
+        }
+        return Ok(book);
+    }
+
+    fn parse_line(line: &str) -> Option<(u64, BookMove)> {
+        let mut fields = line.split_whitespace();
+        let hash = u64::from_str_radix(fields.next()?, 16).ok()?;
+        let mv = fields.next()?.parse().ok()?;
+        let mut numbers = fields.map(|field| field.parse::<u32>().ok());
+        let book_move = BookMove {
+            mv,
+            weight: numbers.next()??,
+            wins: numbers.next()??,
+            draws: numbers.next()??,
+            losses: numbers.next()??
+        };
+        return Some((hash, book_move));
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let mut hashes: Vec<&u64> = self.positions.keys().collect();
+        hashes.sort();
+
+        let mut contents = String::new();
+        for hash in hashes {
+            for m in &self.positions[hash] {
+                contents += &format!(
+                    "{hash:016x} {} {} {} {} {}\n",
+                    m.mv, m.weight, m.wins, m.draws, m.losses
+                );
+            }
+        }
+        return fs::write(path, contents);
+    }
+
+    /// Adds the first `plies` moves of a finished game.  Unfinished games are
+    /// skipped because they have no result to count.
+    pub fn add_game(&mut self, game: &Game, plies: usize) -> bool {
+        if !game.is_over() {
+            return false;
+        }
+        let history = game.get_history();
+        let winner = game.get_result().unwrap().winner;
+
+        for window in history.windows(2).take(plies) {
+            let (_, board, (red_inv, black_inv)) = &window[0];
+            let mv = window[1].0.unwrap();
+            let hash = board.position_hash(red_inv, black_inv);
+
+            let moves = self.positions.entry(hash).or_default();
+            let book_move = match moves.iter().position(|m| m.mv == mv) {
+                Some(i) => &mut moves[i],
+                None => {
+                    moves.push(BookMove {mv, weight: 0, wins: 0, draws: 0, losses: 0});
+                    moves.last_mut().unwrap()
+                }
+            };
+            book_move.weight += 1;
+            match winner {
+                None => book_move.draws += 1,
+                Some(color) if color == board.color => book_move.wins += 1,
+                Some(_) => book_move.losses += 1
+            }
+        }
+        return true;
+    }
+
+    pub fn get_moves(&self, hash: u64) -> &[BookMove] {
+        return self.positions.get(&hash).map_or(&[], |moves| moves.as_slice());
+    }
+
+    /// Picks one of the book moves for a position at random, in proportion to its weight.
+    pub fn choose_move(&self, hash: u64, seed: u64) -> Option<Move> {
+        let moves = self.get_moves(hash);
+        let total: u64 = moves.iter().map(|m| m.weight as u64).sum();
+        if total == 0 {
+            return None;
+        }
+
+        // splitmix64, so nearby seeds still pick different moves
+        let mut r = seed.wrapping_add(0x9e3779b97f4a7c15);
+        r = (r ^ (r >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        r = (r ^ (r >> 27)).wrapping_mul(0x94d049bb133111eb);
+        let mut r = (r ^ (r >> 31)) % total;
+
+        for m in moves {
+            if r < m.weight as u64 {
+                return Some(m.mv);
+            }
+            r -= m.weight as u64;
+        }
+        return None;
+    }
+
+    pub fn len(&self) -> usize {
+        return self.positions.len();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.positions.is_empty();
+    }
+}
+
+pub fn update_book_panel(
+    book: Res<Book>,
+    board: Query<&Board>,
+    inventory: Query<(&Inventory, &Color)>,
+    mut panel: Query<&mut Text, With<BookUI>>,
+) {
+    let red_inv = inventory.iter().find(|e| *e.1 == Color::Red).unwrap().0;
+    let black_inv = inventory.iter().find(|e| *e.1 == Color::Black).unwrap().0;
+    let hash = board.single().position_hash(red_inv, black_inv);
+
+    let mut moves = book.get_moves(hash).to_vec();
+    moves.sort_by(|a, b| b.weight.cmp(&a.weight));
+
+    let mut value = String::from("Book\n");
+    if moves.is_empty() {
+        value += "no moves";
+    }
+    for m in moves {
+        let games = (m.wins + m.draws + m.losses).max(1) as f32;
+        value += &format!(
+            "{}  x{}  W {:.0}% D {:.0}% L {:.0}%\n",
+            m.mv, m.weight,
+            m.wins as f32 / games * 100.,
+            m.draws as f32 / games * 100.,
+            m.losses as f32 / games * 100.
+        );
+    }
+    panel.single_mut().sections[0].value = value;
+}
@@ -0,0 +1,210 @@
+use std::{sync::Arc, time::{SystemTime, UNIX_EPOCH}};
+
+use bevy::{prelude::*, tasks::{block_on, poll_once, AsyncComputeTaskPool, Task}};
+
+use crate::{
+    board::{Board, Inventory},
+    book::Book,
+    engine::Engines,
+    eval::Evaluator,
+    events::{GameEnded, HistoryNavigated, PlaceRequested},
+    game::{Game, Move},
+    search::{search, SearchConfig, SearchProgress},
+    tile::Color
+};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Player {
+    Human,
+    Bot,
+    /// An external program, see `Engine`.
+    Engine,
+    /// Plays from another instance over the network.
+    Remote
+}
+
+#[derive(Resource, Clone, Copy)]
+pub struct Players {
+    pub red: Player,
+    pub black: Player
+}
+
+impl Players {
+    pub fn get(&self, color: Color) -> Player {
+        match color {
+            Color::Black => self.black,
+            _ => self.red
+        }
+    }
+
+    pub fn has_remote(&self) -> bool {
+        return self.red == Player::Remote || self.black == Player::Remote;
+    }
+
+    pub fn set(&mut self, color: Color, player: Player) {
+        match color {
+            Color::Black => self.black = player,
+            _ => self.red = player
+        }
+    }
+
+    pub fn toggle(&mut self, color: Color) {
+        let player = match self.get(color) {
+            Player::Human => Player::Bot,
+            Player::Bot => Player::Human,
+            player => player
+        };
+        self.set(color, player);
+    }
+}
+
+/// What the players that are not human choose their moves with.
+#[derive(Default)]
+pub struct Computer {
+    pub book: Book,
+    pub evaluator: Evaluator,
+    pub config: SearchConfig,
+    pub engines: Engines
+}
+
+impl Computer {
+    /// Adds everything the bot and engine systems need to `app`.
+    pub fn insert(self, app: &mut App) {
+        app.insert_resource(self.book)
+            .insert_resource(self.evaluator)
+            .insert_resource(self.config)
+            .insert_resource(self.engines)
+            .init_resource::<BotTask>();
+    }
+}
+
+struct Thinking {
+    task: Task<Option<Move>>,
+    progress: Arc<SearchProgress>,
+    position: u64
+}
+
+/// The search a bot is currently running on the async compute task pool, if any.
+#[derive(Resource, Default)]
+pub struct BotTask(Option<Thinking>);
+
+impl BotTask {
+    /// Stops the running search and throws away its result.
+    pub fn cancel(&mut self) {
+        if let Some(thinking) = self.0.take() {
+            thinking.progress.cancel();
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct BotUI;
+
+/// A move the book suggests for the position, if it is legal.
+/// A book learned under other rules can suggest moves illegal here.
+fn book_move(book: &Book, b: &Board, red_inv: &Inventory, black_inv: &Inventory) -> Option<Move> {
+    let seed = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_nanos() as u64);
+    let inv = if b.color == Color::Red {red_inv} else {black_inv};
+    return book.choose_move(b.position_hash(red_inv, black_inv), seed)
+        .filter(|mv| inv.can_place_face(mv.face) && b.check_tile(mv.x, mv.y, mv.face).is_ok());
+}
+
+/// Picks a move from the book, or searches for one, waiting for the search to finish.
+pub fn choose_move(
+    book: &Book, evaluator: &Evaluator, config: &SearchConfig,
+    b: &Board, red_inv: &Inventory, black_inv: &Inventory
+) -> Option<Move> {
+    if let Some(mv) = book_move(book, b, red_inv, black_inv) {
+        return Some(mv);
+    }
+    return search(b, red_inv, black_inv, evaluator, config, &SearchProgress::default());
+}
+
+pub fn bot_system(
+    players: Res<Players>,
+    book: Res<Book>,
+    evaluator: Res<Evaluator>,
+    config: Res<SearchConfig>,
+    mut bot_task: ResMut<BotTask>,
+    mut navigated: EventReader<HistoryNavigated>,
+    mut ended: EventReader<GameEnded>,
+    mut places: EventWriter<PlaceRequested>,
+    game: Query<&Game>,
+    board: Query<&Board>,
+    inventory: Query<(&Inventory, &Color)>,
+) {
+    // a search for a position that is no longer on the board is wasted
+    if navigated.read().count() + ended.read().count() > 0 {
+        bot_task.cancel();
+    }
+
+    let red_inv = inventory.iter().find(|e| *e.1 == Color::Red).unwrap().0.clone();
+    let black_inv = inventory.iter().find(|e| *e.1 == Color::Black).unwrap().0.clone();
+    let b = board.single().clone();
+    let position = b.position_hash(&red_inv, &black_inv);
+
+    if let Some(thinking) = &mut bot_task.0 {
+        let Some(result) = block_on(poll_once(&mut thinking.task)) else {
+            return;
+        };
+        let searched = thinking.position;
+        bot_task.0 = None;
+
+        if let Some(mv) = result.filter(|_| searched == position) {
+            places.send(PlaceRequested {mv, live: true});
+        }
+        return;
+    }
+
+    let g = game.single();
+    if players.get(b.color) != Player::Bot || g.get_pos() + 1 != g.get_history().len() || g.is_over() {
+        return;
+    }
+
+    if let Some(mv) = book_move(&book, &b, &red_inv, &black_inv) {
+        places.send(PlaceRequested {mv, live: true});
+        return;
+    }
+
+    let progress = Arc::new(SearchProgress::default());
+    let task_progress = progress.clone();
+    let evaluator = evaluator.clone();
+    let config = config.clone();
+    let task = AsyncComputeTaskPool::get().spawn(async move {
+        search(&b, &red_inv, &black_inv, &evaluator, &config, &task_progress)
+    });
+    bot_task.0 = Some(Thinking {task, progress, position});
+}
+
+pub fn update_bot_panel(
+    players: Res<Players>,
+    bot_task: Res<BotTask>,
+    engines: Res<Engines>,
+    board: Query<&Board>,
+    mut panel: Query<&mut Text, With<BotUI>>,
+) {
+    let name = |player: Player| match player {
+        Player::Human => "human",
+        Player::Bot => "bot",
+        Player::Engine => "engine",
+        Player::Remote => "remote"
+    };
+    let mut value = format!("Red: {}  Black: {}", name(players.red), name(players.black));
+    if let Some(error) = &engines.error {
+        value += &format!("\n{error}");
+    }
+
+    if let Some(thinking) = &bot_task.0 {
+        let color = match board.single().color {
+            Color::Black => "Black",
+            _ => "Red"
+        };
+        value += &format!(
+            "\n{color} is thinking: depth {}, {} nodes, best {}",
+            thinking.progress.get_depth(),
+            thinking.progress.get_nodes(),
+            thinking.progress.get_best().map_or("-".to_string(), |mv| mv.to_string())
+        );
+    }
+    panel.single_mut().sections[0].value = value;
+}
@@ -0,0 +1,167 @@
+use bevy::{input::mouse::{MouseScrollUnit, MouseWheel}, prelude::*};
+
+use crate::{
+    game::Game,
+    net::{NetState, Network, Role},
+    server::MAX_CHAT_LEN
+};
+
+/// Pixels scrolled for each line of a mouse wheel.
+const SCROLL_LINE: f32 = 20.;
+
+/// Messages between the players and spectators of a network game.
+#[derive(Resource, Default)]
+pub struct Chat {
+    /// Who said what, oldest first.  Notes from the game itself have no speaker.
+    pub messages: Vec<(Option<String>, String)>,
+    /// What is being typed, while the chat has the keyboard.
+    pub input: Option<String>,
+    /// Speakers whose messages are not shown here.
+    pub muted: Vec<String>,
+    /// How far the panel is scrolled back from the latest message, in pixels.
+    scroll: f32
+}
+
+impl Chat {
+    pub fn receive(&mut self, speaker: &str, text: &str) {
+        self.messages.push((Some(speaker.to_string()), text.to_string()));
+    }
+
+    fn note(&mut self, text: String) {
+        self.messages.push((None, text));
+    }
+
+    /// Hides or shows again what `speaker` says, including what they already said.
+    pub fn mute(&mut self, speaker: &str, mute: bool) {
+        self.muted.retain(|muted| muted != speaker);
+        if mute {
+            self.muted.push(speaker.to_string());
+        }
+        self.note(format!("{} {speaker}", if mute {"muted"} else {"unmuted"}));
+    }
+}
+
+/// The whole chat panel, shown in network games.
+#[derive(Component)]
+pub struct ChatUI;
+
+/// The part of the panel the messages scroll in.
+#[derive(Component)]
+pub struct ChatPanel;
+
+#[derive(Component)]
+pub struct ChatText;
+
+#[derive(Component)]
+pub struct ChatInput;
+
+/// Enter starts a message and sends it, Escape drops it.  While a message is
+/// being typed the chat has the keyboard to itself.  `/mute <speaker>` and
+/// `/unmute <speaker>` hide and show what someone says, on this screen only.
+/// The mouse wheel scrolls the panel.
+pub fn chat_system(
+    network: Option<ResMut<Network>>,
+    mut chat: ResMut<Chat>,
+    mut keyboard_input: ResMut<ButtonInput<KeyCode>>,
+    mut characters: EventReader<ReceivedCharacter>,
+    mut wheel: EventReader<MouseWheel>,
+    mut game: Query<&mut Game>,
+    panel: Query<&Interaction, With<ChatPanel>>,
+) {
+    let Some(mut network) = network else {
+        characters.clear();
+        wheel.clear();
+        return;
+    };
+
+    if panel.get_single().is_ok_and(|interaction| *interaction != Interaction::None) {
+        for event in wheel.read() {
+            let dy = match event.unit {
+                MouseScrollUnit::Line => event.y * SCROLL_LINE,
+                MouseScrollUnit::Pixel => event.y
+            };
+            chat.scroll = (chat.scroll + dy).max(0.);
+        }
+    } else {
+        wheel.clear();
+    }
+
+    let Some(mut input) = chat.input.take() else {
+        characters.clear();
+        if keyboard_input.just_pressed(KeyCode::Enter) {
+            chat.input = Some(String::new());
+            keyboard_input.reset_all();
+        }
+        return;
+    };
+    for character in characters.read() {
+        for c in character.char.chars().filter(|c| !c.is_control()) {
+            if input.chars().count() < MAX_CHAT_LEN {
+                input.push(c);
+            }
+        }
+    }
+    if keyboard_input.just_pressed(KeyCode::Backspace) {
+        input.pop();
+    }
+    let send = keyboard_input.just_pressed(KeyCode::Enter);
+    let cancel = keyboard_input.just_pressed(KeyCode::Escape);
+    keyboard_input.reset_all();
+    if cancel {
+        return;
+    }
+    if !send {
+        chat.input = Some(input);
+        return;
+    }
+
+    let text = input.trim();
+    if let Some(speaker) = text.strip_prefix("/mute ") {
+        chat.mute(speaker.trim(), true);
+    } else if let Some(speaker) = text.strip_prefix("/unmute ") {
+        chat.mute(speaker.trim(), false);
+    } else if !text.is_empty() {
+        if network.role != Role::Host && network.state != NetState::Connected {
+            chat.note("not connected, the message was not sent".to_string());
+            return;
+        }
+        network.chat(text);
+        // everyone else hears it from the host, so the host keeps its own messages
+        if network.role == Role::Host {
+            game.single_mut().comment(format!("red: {text}"));
+            chat.receive("red", text);
+        }
+        chat.scroll = 0.;
+    }
+}
+
+pub fn update_chat_panel(
+    network: Option<Res<Network>>,
+    mut chat: ResMut<Chat>,
+    mut ui: Query<&mut Visibility, With<ChatUI>>,
+    panel: Query<&Node, With<ChatPanel>>,
+    mut text: Query<(&mut Text, &mut Style, &Node), (With<ChatText>, Without<ChatInput>)>,
+    mut input: Query<&mut Text, (With<ChatInput>, Without<ChatText>)>,
+) {
+    *ui.single_mut() = if network.is_some() {Visibility::Inherited} else {Visibility::Hidden};
+
+    let (mut text, mut style, node) = text.single_mut();
+    let lines: Vec<String> = chat.messages.iter()
+        .filter(|(speaker, _)| speaker.as_ref().is_none_or(|speaker| !chat.muted.contains(speaker)))
+        .map(|(speaker, text)| match speaker {
+            Some(speaker) => format!("{speaker}: {text}"),
+            None => format!("({text})")
+        })
+        .collect();
+    text.sections[0].value = lines.join("\n");
+
+    // keep the latest message at the bottom unless the panel was scrolled back
+    let max_scroll = (node.size().y - panel.single().size().y).max(0.);
+    chat.scroll = chat.scroll.min(max_scroll);
+    style.top = Val::Px(chat.scroll - max_scroll);
+
+    input.single_mut().sections[0].value = match &chat.input {
+        Some(input) => format!("> {input}_"),
+        None => "Enter to chat".to_string()
+    };
+}
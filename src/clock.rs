@@ -0,0 +1,181 @@
+use std::{fmt, fs, io, path::Path, str::FromStr, time::Duration};
+
+use bevy::prelude::*;
+
+use crate::{game::Game, tile::Color};
+
+/// What a player gets back for each move.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Bonus {
+    /// Fischer: added after every move.
+    Increment(Duration),
+    /// Bronstein: time spent on a move is given back, up to the delay.
+    Delay(Duration)
+}
+
+/// Written as the initial seconds followed by `+` and the increment
+/// or `d` and the delay, e.g. `300+3` or `300d3`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TimeControl {
+    pub initial: Duration,
+    pub bonus: Bonus
+}
+
+impl TimeControl {
+    /// Reads a file of `name = value` lines: `initial_s` and either `increment_s` or `delay_s`.
+    pub fn load(path: impl AsRef<Path>) -> io::Result<TimeControl> {
+        let mut initial = None;
+        let mut bonus = None;
+        for line in fs::read_to_string(path)?.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let invalid = || io::Error::new(io::ErrorKind::InvalidData, format!("invalid setting: {line}"));
+
+            let (name, value) = line.split_once('=').ok_or_else(invalid)?;
+            let value = Duration::try_from_secs_f64(value.trim().parse().map_err(|_| invalid())?).map_err(|_| invalid())?;
+            match name.trim() {
+                "initial_s" => initial = Some(value),
+                "increment_s" if bonus.is_none() => bonus = Some(Bonus::Increment(value)),
+                "delay_s" if bonus.is_none() => bonus = Some(Bonus::Delay(value)),
+                _ => return Err(invalid())
+            }
+        }
+
+        let initial = initial.ok_or(io::Error::new(io::ErrorKind::InvalidData, "missing initial_s"))?;
+        return Ok(TimeControl {initial, bonus: bonus.unwrap_or(Bonus::Increment(Duration::ZERO))});
+    }
+
+    /// The time left after spending `spent` on a move with `remaining` on the clock.
+    pub fn after_move(&self, remaining: Duration, spent: Duration) -> Duration {
+        let left = remaining.saturating_sub(spent);
+        return match self.bonus {
+            Bonus::Increment(increment) => left + increment,
+            Bonus::Delay(delay) => left + spent.min(delay)
+        };
+    }
+}
+
+impl fmt::Display for TimeControl {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return match self.bonus {
+            Bonus::Increment(increment) => write!(f, "{}+{}", self.initial.as_secs_f64(), increment.as_secs_f64()),
+            Bonus::Delay(delay) => write!(f, "{}d{}", self.initial.as_secs_f64(), delay.as_secs_f64())
+        };
+    }
+}
+
+impl FromStr for TimeControl {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<TimeControl, ()> {
+        let secs = |s: &str| s.parse().ok().and_then(|secs| Duration::try_from_secs_f64(secs).ok()).ok_or(());
+        if let Some((initial, increment)) = s.split_once('+') {
+            return Ok(TimeControl {initial: secs(initial)?, bonus: Bonus::Increment(secs(increment)?)});
+        }
+        let (initial, delay) = s.split_once('d').ok_or(())?;
+        return Ok(TimeControl {initial: secs(initial)?, bonus: Bonus::Delay(secs(delay)?)});
+    }
+}
+
+/// Both players' time.  Only the player to move loses time, and only while
+/// `tick` is called; the time spent on the current move counts once it is played.
+#[derive(Clone, Debug)]
+pub struct Clock {
+    pub control: TimeControl,
+    /// Red's then Black's time at the start of their move.
+    remaining: [Duration; 2],
+    /// Time spent on the current move so far.
+    turn: Duration,
+    /// The player who ran out of time.
+    pub flagged: Option<Color>
+}
+
+impl Clock {
+    pub fn new(control: TimeControl) -> Clock {
+        Clock {
+            control,
+            remaining: [control.initial; 2],
+            turn: Duration::ZERO,
+            flagged: None
+        }
+    }
+
+    /// The clock after moves by each color that took the time paired with it.
+    pub fn replay(control: TimeControl, times: impl Iterator<Item = (Color, Duration)>) -> Clock {
+        let mut clock = Clock::new(control);
+        for (color, spent) in times {
+            clock.turn = spent;
+            clock.finish_turn(color);
+        }
+        return clock;
+    }
+
+    /// The time `color` has left, counting the current move if it is theirs to play.
+    pub fn get_remaining(&self, color: Color, to_move: Color) -> Duration {
+        let remaining = self.remaining[(color == Color::Black) as usize];
+        if color != to_move {
+            return remaining;
+        }
+        return match self.control.bonus {
+            Bonus::Increment(_) => remaining.saturating_sub(self.turn),
+            Bonus::Delay(delay) => remaining.saturating_sub(self.turn.saturating_sub(delay))
+        };
+    }
+
+    /// Runs `color`'s clock, flagging them if their time runs out.
+    pub fn tick(&mut self, color: Color, delta: Duration) {
+        if self.flagged.is_some() {
+            return;
+        }
+        self.turn += delta;
+        if self.get_remaining(color, color).is_zero() {
+            self.flagged = Some(color);
+        }
+    }
+
+    /// Ends `color`'s move, returning the time it took.
+    pub fn finish_turn(&mut self, color: Color) -> Duration {
+        let spent = self.turn;
+        let remaining = &mut self.remaining[(color == Color::Black) as usize];
+        *remaining = self.control.after_move(*remaining, spent);
+        self.turn = Duration::ZERO;
+        return spent;
+    }
+
+    /// Sets the time spent on the current move, e.g. when replaying a record.
+    pub fn set_turn(&mut self, spent: Duration) {
+        self.turn = spent;
+    }
+}
+
+/// Shows a player's clock.
+#[derive(Component)]
+pub struct ClockUI;
+
+/// Runs the clock of the player to move, but only while the last position is on screen.
+pub fn clock_system(time: Res<Time>, mut game: Query<&mut Game>) {
+    let mut g = game.single_mut();
+    if g.get_pos() + 1 == g.get_history().len() {
+        g.tick(time.delta());
+    }
+}
+
+pub fn update_clock_panel(
+    game: Query<&Game>,
+    mut panel: Query<(&mut Text, &Color), With<ClockUI>>,
+) {
+    let g = game.single();
+    let to_move = g.get_history().last().unwrap().1.color;
+    for (mut text, color) in &mut panel {
+        text.sections[0].value = match g.get_clock() {
+            Some(clock) if clock.flagged == Some(*color) => "flag".to_string(),
+            Some(clock) => {
+                let remaining = clock.get_remaining(*color, to_move);
+                format!("{}:{:02}", remaining.as_secs() / 60, remaining.as_secs() % 60)
+            },
+            None => String::new()
+        };
+    }
+}
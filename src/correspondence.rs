@@ -0,0 +1,381 @@
+use std::{fs, io, path::{Path, PathBuf}, time::UNIX_EPOCH};
+
+use bevy::{prelude::*, window::FileDragAndDrop};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+use crate::{
+    events::{GameReplaced, MovePlayed, NavigateRequested, Navigation, PlaceRequested},
+    game::{Game, Move, RecordError},
+    net::NetUI,
+    protocol::random_id,
+    tile::Color
+};
+
+type Signer = Hmac<Sha256>;
+
+#[derive(Debug)]
+pub enum CorrespondenceError {
+    Io(io::Error),
+    /// The key file is empty.
+    Key,
+    Format,
+    /// The file was not signed with the same key, or was changed after it was signed.
+    Signature,
+    Record(RecordError),
+    /// The file belongs to another game.
+    WrongGame,
+    /// The file does not carry the next move, e.g. it was already imported or one was skipped.
+    OutOfOrder {expected: usize, found: usize},
+    /// The file's history is not the game played so far.
+    Diverged,
+    /// The new move is not the opponent's.
+    WrongTurn
+}
+
+/// A game sent between players as a file after every move.  It is a game record
+/// followed by the game id, the new move and a signature, e.g.
+///
+/// ```text
+/// 7x7
+/// Kd3
+/// Qc4
+/// # game 3f1c2a9e8b7d6c5a
+/// # move 2 Qc4
+/// # signature 5e0f...
+/// ```
+///
+/// The signature is an HMAC-SHA256 of everything before it, keyed with a secret
+/// both players share, so the file cannot be changed without the key.
+pub struct MoveFile {
+    pub game_id: u64,
+    pub game: Game
+}
+
+impl MoveFile {
+    pub fn write(&self, key: &[u8]) -> String {
+        let history = self.game.get_history();
+        let mut text = self.game.to_record();
+        text += &format!("# game {:016x}\n", self.game_id);
+        text += &format!("# move {} {}\n", history.len() - 1, history.last().unwrap().0.unwrap());
+        let signature: String = sign(key, &text).iter().map(|byte| format!("{byte:02x}")).collect();
+        return text + &format!("# signature {signature}\n");
+    }
+
+    pub fn read(text: &str, key: &[u8]) -> Result<MoveFile, CorrespondenceError> {
+        let (signed, signature) = text.trim_end().rsplit_once('\n').ok_or(CorrespondenceError::Format)?;
+        let signed = format!("{signed}\n");
+        let signature = signature.strip_prefix("# signature ").ok_or(CorrespondenceError::Format)?;
+        let signature: Option<Vec<u8>> = (0..signature.len()).step_by(2)
+            .map(|i| signature.get(i..i + 2).and_then(|byte| u8::from_str_radix(byte, 16).ok()))
+            .collect();
+        let mut signer = Signer::new_from_slice(key).unwrap();
+        signer.update(signed.as_bytes());
+        signer.verify_slice(&signature.ok_or(CorrespondenceError::Format)?)
+            .map_err(|_| CorrespondenceError::Signature)?;
+
+        let game = Game::from_record(&signed).map_err(CorrespondenceError::Record)?;
+        let mut game_id = None;
+        let mut new_move = None;
+        for line in signed.lines() {
+            if let Some(id) = line.strip_prefix("# game ") {
+                game_id = u64::from_str_radix(id, 16).ok();
+            } else if let Some(mv) = line.strip_prefix("# move ") {
+                new_move = mv.split_once(' ')
+                    .and_then(|(ply, mv)| Some((ply.parse::<usize>().ok()?, mv.parse::<Move>().ok()?)));
+            }
+        }
+
+        // the move line has to agree with the end of the history
+        let history = game.get_history();
+        let Some((ply, mv)) = new_move else {
+            return Err(CorrespondenceError::Format);
+        };
+        if ply == 0 || ply + 1 != history.len() || history[ply].0 != Some(mv) {
+            return Err(CorrespondenceError::Format);
+        }
+        return Ok(MoveFile {
+            game_id: game_id.ok_or(CorrespondenceError::Format)?,
+            game
+        });
+    }
+
+    pub fn file_name(&self) -> String {
+        return format!("{:016x}-{:03}.move", self.game_id, self.game.get_history().len() - 1);
+    }
+
+    /// Checks that the file carries the opponent's reply to `game`, and returns it.
+    pub fn check(&self, game_id: Option<u64>, game: &Game, color: Color) -> Result<Move, CorrespondenceError> {
+        let local = game.get_history();
+        let history = self.game.get_history();
+        if game_id.is_some_and(|id| id != self.game_id) || history[0].1.get_size() != local[0].1.get_size() {
+            return Err(CorrespondenceError::WrongGame);
+        }
+        if history.len() != local.len() + 1 {
+            return Err(CorrespondenceError::OutOfOrder {expected: local.len(), found: history.len() - 1});
+        }
+        if local.iter().zip(history).any(|(a, b)| a.0 != b.0) {
+            return Err(CorrespondenceError::Diverged);
+        }
+        if local.last().unwrap().1.color == color {
+            return Err(CorrespondenceError::WrongTurn);
+        }
+        return Ok(history.last().unwrap().0.unwrap());
+    }
+}
+
+fn sign(key: &[u8], text: &str) -> Vec<u8> {
+    let mut signer = Signer::new_from_slice(key).unwrap();
+    signer.update(text.as_bytes());
+    return signer.finalize().into_bytes().to_vec();
+}
+
+/// A game played by exchanging move files.  Every file written or imported is kept
+/// in `dir`, so the game carries on from the latest one when it is opened again.
+/// A directory can hold several games, told apart by their ids.
+#[derive(Resource)]
+pub struct Correspondence {
+    pub color: Color,
+    dir: PathBuf,
+    key: Vec<u8>,
+    /// Not known to Black until the first move file arrives.
+    game_id: Option<u64>,
+    /// The game found in `dir`, until it is put on the board.
+    loaded: Option<Game>,
+    pub status: String
+}
+
+impl Correspondence {
+    /// Opens the game `game_id` in `dir`, or without one the game played last there.
+    /// Files that cannot be read or verified are left out and listed in the status.
+    pub fn open(
+        color: Color,
+        dir: impl AsRef<Path>,
+        key_path: impl AsRef<Path>,
+        game_id: Option<u64>
+    ) -> Result<Correspondence, CorrespondenceError> {
+        let key = fs::read(key_path).map_err(CorrespondenceError::Io)?;
+        let key = key.trim_ascii().to_vec();
+        if key.is_empty() {
+            return Err(CorrespondenceError::Key);
+        }
+        fs::create_dir_all(&dir).map_err(CorrespondenceError::Io)?;
+
+        let mut found = Vec::new();
+        let mut skipped = Vec::new();
+        for entry in fs::read_dir(&dir).map_err(CorrespondenceError::Io)? {
+            let path = entry.map_err(CorrespondenceError::Io)?.path();
+            if path.extension().is_none_or(|extension| extension != "move") {
+                continue;
+            }
+            let file = fs::read_to_string(&path)
+                .map_err(CorrespondenceError::Io)
+                .and_then(|text| MoveFile::read(&text, &key));
+            match file {
+                Ok(file) => {
+                    let modified = fs::metadata(&path).and_then(|metadata| metadata.modified()).unwrap_or(UNIX_EPOCH);
+                    found.push((file, modified));
+                },
+                Err(e) => skipped.push(format!("{}: {e:?}", path.file_name().unwrap().to_string_lossy()))
+            }
+        }
+
+        // carry on from the longest file of the game
+        let game_id = game_id.or(found.iter().max_by_key(|(_, modified)| *modified).map(|(file, _)| file.game_id));
+        let mut others: Vec<u64> = found.iter().map(|(file, _)| file.game_id).filter(|id| Some(*id) != game_id).collect();
+        others.sort();
+        others.dedup();
+        let latest = found.into_iter()
+            .map(|(file, _)| file)
+            .filter(|file| Some(file.game_id) == game_id)
+            .max_by_key(|file| file.game.get_history().len());
+
+        let mut status = match color {
+            Color::Black if latest.is_none() => vec!["Waiting for Red's first move, drop its file here".to_string()],
+            _ => Vec::new()
+        };
+        match others.len() {
+            0 => {},
+            1 => status.push("1 other game here, open it with its game id".to_string()),
+            n => status.push(format!("{n} other games here, open one with its game id"))
+        }
+        if !skipped.is_empty() {
+            status.push(format!("Skipped {}", skipped.join(", ")));
+        }
+        let game_id = match (game_id, color) {
+            (Some(game_id), _) => Some(game_id),
+            (None, Color::Black) => None,
+            (None, _) => Some(random_id())
+        };
+        return Ok(Correspondence {
+            color,
+            dir: dir.as_ref().to_path_buf(),
+            key,
+            game_id,
+            loaded: latest.map(|file| file.game),
+            status: status.join("\n")
+        });
+    }
+
+    /// Reads a move file, checks it against `game` and keeps a copy of it.
+    fn import(&mut self, path: &Path, game: &Game) -> Result<Move, CorrespondenceError> {
+        let text = fs::read_to_string(path).map_err(CorrespondenceError::Io)?;
+        let file = MoveFile::read(&text, &self.key)?;
+        let mv = file.check(self.game_id, game, self.color)?;
+        fs::write(self.dir.join(file.file_name()), text).map_err(CorrespondenceError::Io)?;
+        self.game_id = Some(file.game_id);
+        return Ok(mv);
+    }
+
+    fn export(&self, game: &Game) -> io::Result<PathBuf> {
+        let file = MoveFile {game_id: self.game_id.unwrap(), game: game.clone()};
+        let path = self.dir.join(file.file_name());
+        fs::write(&path, file.write(&self.key))?;
+        return Ok(path);
+    }
+}
+
+/// Writes a move file after every local move and plays the moves of files dropped on the window.
+pub fn correspondence_system(
+    correspondence: Option<ResMut<Correspondence>>,
+    mut drops: EventReader<FileDragAndDrop>,
+    mut played: EventReader<MovePlayed>,
+    mut places: EventWriter<PlaceRequested>,
+    mut navigate: EventWriter<NavigateRequested>,
+    mut replacements: EventWriter<GameReplaced>,
+    game: Query<&Game>,
+) {
+    let Some(mut correspondence) = correspondence else {
+        return;
+    };
+    if let Some(loaded) = correspondence.loaded.take() {
+        replacements.send(GameReplaced(loaded));
+        navigate.send(NavigateRequested(Navigation::Last));
+        // what is on the board is the game once it is replaced
+        return;
+    }
+    let g = game.single();
+
+    for MovePlayed {color, ..} in played.read() {
+        if *color != correspondence.color {
+            continue;
+        }
+        correspondence.status = match correspondence.export(g) {
+            Ok(path) => format!("Send {} to your opponent", path.display()),
+            Err(e) => format!("Could not write the move file: {e}")
+        };
+    }
+
+    for drop in drops.read() {
+        let FileDragAndDrop::DroppedFile {path_buf, ..} = drop else {
+            continue;
+        };
+        match correspondence.import(path_buf, g) {
+            Ok(mv) => {
+                navigate.send(NavigateRequested(Navigation::Last));
+                places.send(PlaceRequested {mv, live: true});
+                correspondence.status = format!("Played {mv} from {}", path_buf.display());
+            },
+            Err(e) => correspondence.status = format!("Rejected {}: {e:?}", path_buf.display())
+        }
+    }
+}
+
+pub fn update_correspondence_panel(
+    correspondence: Option<Res<Correspondence>>,
+    mut panel: Query<&mut Text, With<NetUI>>,
+) {
+    let Some(correspondence) = correspondence else {
+        return;
+    };
+    let color = match correspondence.color {
+        Color::Black => "Black",
+        _ => "Red"
+    };
+    let game_id = correspondence.game_id.map_or(String::new(), |id| format!(" {id:016x}"));
+    panel.single_mut().sections[0].value = format!("Correspondence game{game_id}, you are {color}\n{}", correspondence.status);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{game::Ruleset, tile::Face};
+
+    const KEY: &[u8] = b"shared secret";
+
+    /// A game of `plies` moves, each the first legal one found.
+    fn game(plies: usize) -> Game {
+        let mut game = Game::new(Ruleset::default());
+        for _ in 0..plies {
+            let mv = [Face::Ace, Face::King, Face::Queen, Face::Jack].into_iter()
+                .flat_map(|face| (0..49).map(move |i| Move {x: i % 7, y: i / 7, face}))
+                .find(|mv| game.check(*mv).is_ok())
+                .unwrap();
+            game.play(mv).unwrap();
+        }
+        return game;
+    }
+
+    fn file(game_id: u64, plies: usize) -> MoveFile {
+        return MoveFile {game_id, game: game(plies)};
+    }
+
+    /// An empty directory of its own for each test.
+    fn dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("descend-{name}-{:016x}", random_id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("key"), KEY).unwrap();
+        return dir;
+    }
+
+    #[test]
+    fn flipped_byte_is_rejected() {
+        let text = file(1, 1).write(KEY);
+        let mut bytes = text.into_bytes();
+        let i = bytes.iter().position(|byte| *byte == b'\n').unwrap() + 1;
+        bytes[i] ^= 0x20;
+        let text = String::from_utf8(bytes).unwrap();
+        assert!(matches!(MoveFile::read(&text, KEY), Err(CorrespondenceError::Signature)));
+    }
+
+    #[test]
+    fn wrong_key_is_rejected() {
+        let text = file(1, 1).write(b"another secret");
+        assert!(matches!(MoveFile::read(&text, KEY), Err(CorrespondenceError::Signature)));
+    }
+
+    #[test]
+    fn replayed_file_is_out_of_order() {
+        let old = MoveFile::read(&file(1, 1).write(KEY), KEY).unwrap();
+        let result = old.check(Some(1), &game(2), Color::Black);
+        assert!(matches!(result, Err(CorrespondenceError::OutOfOrder {expected: 3, found: 1})));
+    }
+
+    #[test]
+    fn file_of_another_game_is_rejected() {
+        let other = MoveFile::read(&file(2, 3).write(KEY), KEY).unwrap();
+        assert!(matches!(other.check(Some(1), &game(2), Color::Black), Err(CorrespondenceError::WrongGame)));
+        assert_eq!(other.check(Some(2), &game(2), Color::Black).ok(), game(3).get_history()[3].0);
+    }
+
+    #[test]
+    fn open_skips_bad_files_and_other_games() {
+        let dir = dir("open");
+        for file in [file(1, 1), file(1, 2), file(2, 3)] {
+            fs::write(dir.join(file.file_name()), file.write(KEY)).unwrap();
+        }
+        fs::write(dir.join("0000000000000001-009.move"), "7x7\nKd3\n# signature 00\n").unwrap();
+        fs::write(dir.join("0000000000000001-010.move"), file(1, 3).write(b"another secret")).unwrap();
+
+        let correspondence = Correspondence::open(Color::Red, &dir, dir.join("key"), Some(1)).unwrap();
+        assert_eq!(correspondence.game_id, Some(1));
+        assert_eq!(correspondence.loaded.as_ref().map(|game| game.get_history().len()), Some(3));
+        assert!(correspondence.status.contains("1 other game here"));
+        assert!(correspondence.status.contains("0000000000000001-009.move: Signature"));
+        assert!(correspondence.status.contains("0000000000000001-010.move: Signature"));
+
+        let correspondence = Correspondence::open(Color::Black, &dir, dir.join("key"), Some(2)).unwrap();
+        assert_eq!(correspondence.loaded.as_ref().map(|game| game.get_history().len()), Some(4));
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
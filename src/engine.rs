@@ -0,0 +1,178 @@
+use std::{
+    collections::VecDeque,
+    io::{self, BufRead, BufReader, ErrorKind, Write},
+    process::{Child, ChildStdin, Command, Stdio},
+    sync::{mpsc::{channel, Receiver, TryRecvError}, Mutex},
+    thread
+};
+
+use bevy::prelude::*;
+
+use crate::{
+    bot::{Player, Players},
+    events::PlaceRequested,
+    game::{Game, Move, Position},
+    tile::Color
+};
+
+/// An external program playing one color.  Whenever it is to move it is sent
+/// `position <position>` on a line of its own, in the notation of `Position`, and
+/// answers with its move on a line of its own, e.g. `Kd3`.
+pub struct Engine {
+    child: Child,
+    stdin: ChildStdin,
+    replies: Mutex<Receiver<String>>,
+    /// Positions it was asked about and has not answered yet, oldest first.
+    asked: VecDeque<u64>
+}
+
+impl Engine {
+    /// Runs `command`, a program followed by its arguments separated by spaces.
+    pub fn start(command: &str) -> io::Result<Engine> {
+        let mut parts = command.split_whitespace();
+        let program = parts.next().ok_or(ErrorKind::InvalidInput)?;
+        let mut child = Command::new(program)
+            .args(parts)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()?;
+        let stdin = child.stdin.take().unwrap();
+        let stdout = child.stdout.take().unwrap();
+
+        let (sender, receiver) = channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                let Ok(line) = line else {
+                    return;
+                };
+                if sender.send(line).is_err() {
+                    return;
+                }
+            }
+        });
+        return Ok(Engine {child, stdin, replies: Mutex::new(receiver), asked: VecDeque::new()});
+    }
+
+    pub fn ask(&mut self, position: &Position) -> io::Result<()> {
+        writeln!(self.stdin, "position {position}")?;
+        return self.stdin.flush();
+    }
+
+    /// The next answer, if the engine has given one.  Fails once the engine has quit.
+    pub fn try_reply(&self) -> io::Result<Option<String>> {
+        return match self.replies.lock().unwrap().try_recv() {
+            Ok(reply) => Ok(Some(reply)),
+            Err(TryRecvError::Empty) => Ok(None),
+            Err(TryRecvError::Disconnected) => Err(ErrorKind::UnexpectedEof.into())
+        };
+    }
+
+    /// Waits for the next answer.
+    pub fn reply(&self) -> io::Result<String> {
+        return self.replies.lock().unwrap().recv().map_err(|_| ErrorKind::UnexpectedEof.into());
+    }
+
+    /// Asks for a move in `position` and waits for it.
+    pub fn choose_move(&mut self, position: &Position) -> Result<Move, String> {
+        self.ask(position).map_err(|e| format!("could not be asked for a move: {e}"))?;
+        let reply = self.reply().map_err(|_| "quit".to_string())?;
+        return reply.trim().parse().map_err(|_| format!("answered {:?}, which is not a move", reply.trim()));
+    }
+}
+
+impl Drop for Engine {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// The engines playing Red and Black, if any.
+#[derive(Resource, Default)]
+pub struct Engines {
+    pub red: Option<Engine>,
+    pub black: Option<Engine>,
+    /// Why the last engine to fail was taken off the board.
+    pub error: Option<String>
+}
+
+impl Engines {
+    /// Starts the engines run by `commands`, Red's first.
+    pub fn start(commands: &[Option<String>; 2]) -> io::Result<Engines> {
+        let start = |command: &Option<String>| command.as_deref().map(Engine::start).transpose();
+        return Ok(Engines {red: start(&commands[0])?, black: start(&commands[1])?, error: None});
+    }
+
+    pub fn get_mut(&mut self, color: Color) -> Option<&mut Engine> {
+        return match color {
+            Color::Black => self.black.as_mut(),
+            _ => self.red.as_mut()
+        };
+    }
+
+    /// Hands each engine the other color, for a rematch.
+    pub fn swap(&mut self) {
+        std::mem::swap(&mut self.red, &mut self.black);
+    }
+
+    /// Takes the engine playing `color` off the board, leaving the color to a human.
+    pub fn fail(&mut self, color: Color, players: &mut Players, error: String) {
+        match color {
+            Color::Black => self.black = None,
+            _ => self.red = None
+        }
+        players.set(color, Player::Human);
+        let name = if color == Color::Black {"Black"} else {"Red"};
+        self.error = Some(format!("{name}'s engine {error}, a human plays {name} now"));
+    }
+}
+
+pub fn engine_system(
+    mut players: ResMut<Players>,
+    mut engines: ResMut<Engines>,
+    mut places: EventWriter<PlaceRequested>,
+    game: Query<&Game>,
+) {
+    let g = game.single();
+    let color = g.get_move().1.color;
+    if players.get(color) != Player::Engine || g.get_pos() + 1 != g.get_history().len() || g.is_over() {
+        return;
+    }
+    let Some(engine) = engines.get_mut(color) else {
+        return;
+    };
+    let (_, b, (red, black)) = g.get_move();
+    let position = Position {board: b.clone(), red: red.clone(), black: black.clone()};
+
+    let mv = match poll(engine, &position) {
+        Ok(Some(mv)) => mv,
+        Ok(None) => return,
+        Err(e) => {
+            engines.fail(color, &mut players, e);
+            return;
+        }
+    };
+    // an illegal move would be turned down and asked for again forever
+    if g.clone().play_live(mv).is_err() {
+        engines.fail(color, &mut players, format!("played {mv}, which is not legal"));
+        return;
+    }
+    places.send(PlaceRequested {mv, live: true});
+}
+
+/// Asks `engine` about `position` unless it already was, and takes its answer if there is one.
+fn poll(engine: &mut Engine, position: &Position) -> Result<Option<Move>, String> {
+    let hash = position.board.position_hash(&position.red, &position.black);
+    if engine.asked.back() != Some(&hash) {
+        engine.ask(position).map_err(|e| format!("could not be asked for a move: {e}"))?;
+        engine.asked.push_back(hash);
+    }
+    let Some(reply) = engine.try_reply().map_err(|_| "quit".to_string())? else {
+        return Ok(None);
+    };
+    // answers to positions taken back since are thrown away
+    if engine.asked.pop_front() != Some(hash) {
+        return Ok(None);
+    }
+    return reply.trim().parse().map(Some).map_err(|_| format!("answered {:?}, which is not a move", reply.trim()));
+}
@@ -0,0 +1,155 @@
+use std::{fs, io, path::Path};
+
+use bevy::{ecs::{component::Component, query::With, system::{Query, Res, Resource}}, text::Text};
+
+use crate::{board::{Board, Inventory}, tile::{Color, Face, Tile}};
+
+pub const FEATURES: usize = 7;
+
+/// Names used for the weights in a weights file, in the same order as `get_features`.
+pub const FEATURE_NAMES: [&str; FEATURES] = [
+    "longest_line",
+    "lines",
+    "open_ends",
+    "mobility",
+    "aces",
+    "kings",
+    "wild_control"
+];
+
+/// Score given to a finished game, larger than any evaluation of an unfinished one.
+pub const WIN_SCORE: f32 = 10000.;
+
+/// Static evaluation of a position as a weighted sum of features.
+/// Scores are from Red's point of view, so positive means Red is ahead.
+///
+/// Weights files hold one `name = value` per line using the names in `FEATURE_NAMES`.
+/// Features missing from the file keep their default weight.
+#[derive(Resource, Clone, Debug)]
+pub struct Evaluator {
+    pub weights: [f32; FEATURES]
+}
+
+#[derive(Component)]
+pub struct EvalUI;
+
+impl Default for Evaluator {
+    fn default() -> Evaluator {
+        Evaluator {
+            weights: [8., 1., 0.5, 0.1, 1.5, 0.5, 0.75]
+        }
+    }
+}
+
+impl Evaluator {
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Evaluator> {
+        let mut evaluator = Evaluator::default();
+        for line in fs::read_to_string(path)?.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let invalid = || io::Error::new(io::ErrorKind::InvalidData, format!("invalid weight: {line}"));
+
+            let (name, value) = line.split_once('=').ok_or_else(invalid)?;
+            let index = FEATURE_NAMES.iter()
+                .position(|n| *n == name.trim())
+                .ok_or_else(invalid)?;
+            evaluator.weights[index] = value.trim().parse().map_err(|_| invalid())?;
+        }
+        return Ok(evaluator);
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let mut contents = String::new();
+        for (name, weight) in FEATURE_NAMES.iter().zip(self.weights) {
+            contents += &format!("{name} = {weight}\n");
+        }
+        return fs::write(path, contents);
+    }
+
+    pub fn evaluate(&self, board: &Board, red_inv: &Inventory, black_inv: &Inventory) -> f32 {
+        if red_inv.is_empty() && black_inv.is_empty() {
+            return match board.get_winner() {
+                Some(Color::Red) => WIN_SCORE,
+                Some(_) => -WIN_SCORE,
+                None => 0.
+            };
+        }
+
+        return get_features(board, red_inv, black_inv).iter()
+            .zip(self.weights)
+            .map(|(feature, weight)| feature * weight)
+            .sum();
+    }
+}
+
+/// Every feature is Red's value minus Black's value.
+pub fn get_features(board: &Board, red_inv: &Inventory, black_inv: &Inventory) -> [f32; FEATURES] {
+    let mut features = [0.; FEATURES];
+    let sign = |color: Color| if color == Color::Red {1.} else {-1.};
+
+    // longest line, lines weighted by length and lines that can still grow
+    let mut longest = [0, 0];
+    for line in board.get_line_segments() {
+        let s = sign(line.color);
+        let i = (line.color == Color::Red) as usize;
+        longest[i] = longest[i].max(line.len);
+        features[1] += s * ((line.len - 1) * (line.len - 1)) as f32;
+
+        let (x, y) = (line.start.0 as i32, line.start.1 as i32);
+        let len = line.len as i32;
+        for (ex, ey) in [
+            (x - line.dir.0, y - line.dir.1),
+            (x + line.dir.0 * len, y + line.dir.1 * len)
+        ] {
+            if ex >= 0 && ey >= 0 && board.get_tile(ex as u32, ey as u32).is_ok_and(|t| t == Tile::None) {
+                features[2] += s * line.len as f32;
+            }
+        }
+    }
+    features[0] = longest[1] as f32 - longest[0] as f32;
+
+    // mobility, as if each player were the one to move
+    let mut red_board = board.clone();
+    red_board.color = Color::Red;
+    let mut black_board = board.clone();
+    black_board.color = Color::Black;
+    features[3] = red_board.get_legal_moves(red_inv).len() as f32
+        - black_board.get_legal_moves(black_inv).len() as f32;
+
+    // high value faces still in hand
+    features[4] = red_inv.get_num_face(Face::Ace) as f32 - black_inv.get_num_face(Face::Ace) as f32;
+    features[5] = red_inv.get_num_face(Face::King) as f32 - black_inv.get_num_face(Face::King) as f32;
+
+    // cards around the Wild
+    let (size_x, size_y) = board.get_size();
+    let wild = (0..size_x * size_y)
+        .map(|i| (i % size_x, i / size_x))
+        .find(|(x, y)| board.get_tile(*x, *y).is_ok_and(|t| t == Tile::Card(Face::Wild, Color::Both)));
+    if let Some((wx, wy)) = wild {
+        for (dx, dy) in [(-1, -1), (0, -1), (1, -1), (-1, 0), (1, 0), (-1, 1), (0, 1), (1, 1)] {
+            let (x, y) = (wx as i32 + dx, wy as i32 + dy);
+            if x < 0 || y < 0 {
+                continue;
+            }
+            if let Ok(Tile::Card(_, color)) = board.get_tile(x as u32, y as u32) {
+                features[6] += sign(color);
+            }
+        }
+    }
+
+    return features;
+}
+
+pub fn update_eval_panel(
+    evaluator: Res<Evaluator>,
+    board: Query<&Board>,
+    inventory: Query<(&Inventory, &Color)>,
+    mut panel: Query<&mut Text, With<EvalUI>>,
+) {
+    let red_inv = inventory.iter().find(|e| *e.1 == Color::Red).unwrap().0;
+    let black_inv = inventory.iter().find(|e| *e.1 == Color::Black).unwrap().0;
+    let score = evaluator.evaluate(board.single(), red_inv, black_inv);
+    panel.single_mut().sections[0].value = format!("Eval: {score:+.2}");
+}
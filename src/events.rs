@@ -0,0 +1,172 @@
+use bevy::prelude::*;
+
+use crate::{
+    board::{Board, Inventory, LineCount},
+    game::{Game, GameResult, Move, MoveError, Offer},
+    tile::{Color, Selected}
+};
+
+/// Asks for a move for the player to move.  A live move is played after the last
+/// move, like `Game::play_live`, otherwise it is played from the position being looked at.
+#[derive(Event, Clone, Copy)]
+pub struct PlaceRequested {
+    pub mv: Move,
+    pub live: bool
+}
+
+/// A requested move that could not be played.
+#[derive(Event)]
+pub struct PlaceRejected {
+    pub mv: Move,
+    pub live: bool,
+    pub error: MoveError
+}
+
+#[derive(Event, Clone, Copy)]
+pub struct MovePlayed {
+    pub mv: Move,
+    /// Who played it.
+    pub color: Color
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Navigation {
+    First,
+    Prev,
+    Next,
+    Last
+}
+
+/// Asks to look at another position of the game.  Whatever replaces the game's
+/// history, e.g. a takeback, asks for the last position so the screen follows.
+#[derive(Event, Clone, Copy)]
+pub struct NavigateRequested(pub Navigation);
+
+/// The position being looked at changed, to the one after `pos` moves.
+#[derive(Event, Clone, Copy)]
+pub struct HistoryNavigated {
+    pub pos: usize
+}
+
+#[derive(Event, Clone, Copy)]
+pub struct GameEnded(pub GameResult);
+
+/// Ends the game before the board decides it, e.g. by resignation or forfeit,
+/// or with the result the host sent.
+#[derive(Event, Clone, Copy)]
+pub struct ResultDeclared(pub GameResult);
+
+/// An offer `from` made was accepted, like `Game::accept`.
+#[derive(Event, Clone, Copy)]
+pub struct OfferAccepted {
+    pub offer: Offer,
+    pub from: Color
+}
+
+/// Replaces the whole game, e.g. with the one the host sent or one read from files.
+#[derive(Event, Clone)]
+pub struct GameReplaced(pub Game);
+
+/// The history changed other than by a move: moves were taken back or the game was replaced.
+#[derive(Event, Clone, Copy)]
+pub struct HistoryReplaced;
+
+/// Registers the events above with `app`.
+pub fn add_events(app: &mut App) {
+    app.add_event::<PlaceRequested>()
+        .add_event::<PlaceRejected>()
+        .add_event::<MovePlayed>()
+        .add_event::<NavigateRequested>()
+        .add_event::<HistoryNavigated>()
+        .add_event::<GameEnded>()
+        .add_event::<ResultDeclared>()
+        .add_event::<OfferAccepted>()
+        .add_event::<GameReplaced>()
+        .add_event::<HistoryReplaced>();
+}
+
+/// Applies a replacement of the game, requested navigation, accepted offers, moves and
+/// declared results to the game, in that order, and copies the position being looked at
+/// to the board, the inventories and the line counts.  Nothing else changes them.
+pub fn apply_system(
+    mut replacements: EventReader<GameReplaced>,
+    mut navigations: EventReader<NavigateRequested>,
+    mut accepted: EventReader<OfferAccepted>,
+    mut places: EventReader<PlaceRequested>,
+    mut results: EventReader<ResultDeclared>,
+    mut game: Query<&mut Game>,
+    mut board: Query<&mut Board>,
+    mut inventory: Query<(&mut Inventory, &Color)>,
+    mut selected: Query<&mut Selected>,
+    mut line_counts: ResMut<LineCount>,
+    mut navigated: EventWriter<HistoryNavigated>,
+    mut played: EventWriter<MovePlayed>,
+    mut rejected: EventWriter<PlaceRejected>,
+    mut ended: EventWriter<GameEnded>,
+    mut replaced: EventWriter<HistoryReplaced>,
+    mut over: Local<bool>,
+) {
+    let mut g = game.single_mut();
+    let mut changed = false;
+    for GameReplaced(game) in replacements.read() {
+        *g = game.clone();
+        replaced.send(HistoryReplaced);
+        changed = true;
+    }
+
+    for NavigateRequested(to) in navigations.read() {
+        match to {
+            Navigation::First => g.first_move(),
+            Navigation::Prev => g.prev_move(),
+            Navigation::Next => g.next_move(),
+            Navigation::Last => g.last_move()
+        };
+        navigated.send(HistoryNavigated {pos: g.get_pos()});
+        changed = true;
+    }
+
+    for OfferAccepted {offer, from} in accepted.read() {
+        if g.accept(*offer, *from) && *offer == Offer::Takeback {
+            replaced.send(HistoryReplaced);
+            changed = true;
+        }
+    }
+
+    for PlaceRequested {mv, live} in places.read() {
+        let (mv, live) = (*mv, *live);
+        let color = if live {g.get_history().last().unwrap()} else {g.get_move()}.1.color;
+        let result = match g.is_over() {
+            true => Err(MoveError::GameOver),
+            false if live => g.play_live(mv),
+            false => g.play(mv)
+        };
+        match result {
+            Ok(()) => {
+                played.send(MovePlayed {mv, color});
+                changed = true;
+            },
+            Err(error) => {
+                rejected.send(PlaceRejected {mv, live, error});
+            }
+        }
+    }
+
+    for ResultDeclared(result) in results.read() {
+        g.end(*result);
+    }
+
+    if changed {
+        let (_, b, (red, black)) = g.get_move();
+        *board.single_mut() = b.clone();
+        *inventory.iter_mut().find(|e| *e.1 == Color::Red).unwrap().0 = red.clone();
+        *inventory.iter_mut().find(|e| *e.1 == Color::Black).unwrap().0 = black.clone();
+        selected.single_mut().face = None;
+        line_counts.0 = b.get_lines();
+    }
+
+    // however it ended: on the board, on time, or by resignation or agreement
+    if let Some(result) = g.get_result().filter(|_| !*over) {
+        ended.send(GameEnded(result));
+    }
+    *over = g.is_over();
+}
@@ -1,20 +1,67 @@
+use std::{fmt, fs, io, path::{Path, PathBuf}, str::FromStr, time::Duration};
+
 use bevy::ecs::component::Component;
+use serde::{Deserialize, Serialize};
+
+use crate::{board::{Board, Inventory, TileError}, clock::{Clock, TimeControl}, tile::{Color, Face, Tile}};
 
-use crate::{board::{Board, Inventory}, tile::Face};
+/// The largest board, so every column has a letter.
+pub const MAX_BOARD_SIZE: u32 = 25;
 
-#[derive(Component)]
+#[derive(Component, Clone)]
 pub struct Game {
+    ruleset: Ruleset,
     moves: Vec<(Option<Move>, Board, (Inventory, Inventory))>,
-    pos: usize
+    pos: usize,
+    clock: Option<Clock>,
+    /// Time spent on each move, alongside `moves`, if the game has a clock.
+    times: Vec<Option<Duration>>,
+    /// How the game ended, if it did before the board decided it.
+    result: Option<GameResult>,
+    /// Comments on the move played from each position, alongside `moves`.
+    comments: Vec<Vec<String>>
 }
 
 impl Game {
-    pub fn new(x: u32, y: u32) -> Game {
+    pub fn new(ruleset: Ruleset) -> Game {
+        let (x, y) = ruleset.size;
+        let cards = ruleset.variant.cards();
         Game {
-            moves: vec![(None, Board::new(x, y), (Inventory::new(3), Inventory::new(3)))],
-            pos: 0
+            ruleset,
+            moves: vec![(None, Board::new(x, y), (Inventory::new(cards), Inventory::new(cards)))],
+            pos: 0,
+            clock: None,
+            times: vec![None],
+            result: None,
+            comments: vec![Vec::new()]
         }
     }
+
+    pub fn with_clock(ruleset: Ruleset, control: TimeControl) -> Game {
+        let mut game = Game::new(ruleset);
+        game.clock = Some(Clock::new(control));
+        return game;
+    }
+
+    /// Starts the game from `position` instead of an empty board, dropping any moves played.
+    pub fn set_start(&mut self, position: Position) {
+        self.ruleset.size = position.board.get_size();
+        self.moves = vec![(None, position.board, (position.red, position.black))];
+        self.times.truncate(1);
+        self.comments.truncate(1);
+        self.pos = 0;
+    }
+
+    pub fn get_ruleset(&self) -> Ruleset {
+        return self.ruleset;
+    }
+
+    /// The position the game started from.
+    pub fn get_start(&self) -> Position {
+        let (_, board, (red, black)) = &self.moves[0];
+        return Position {board: board.clone(), red: red.clone(), black: black.clone()};
+    }
+
     pub fn first_move(&mut self) -> &Board {
         self.pos = 0;
         return &self.moves[self.pos].1;
@@ -36,14 +83,66 @@ impl Game {
         return &self.moves[self.pos].1;
     }
     pub fn add_move(
-        &mut self, x: u32, y: u32, face: Face, board: Board, 
+        &mut self, x: u32, y: u32, face: Face, board: Board,
         red_inv: Inventory, black_inv: Inventory
     ) {
-        self.moves.truncate(self.pos + 1);
+        let color = self.moves[self.pos].1.color;
+        if self.pos + 1 < self.moves.len() {
+            self.moves.truncate(self.pos + 1);
+            self.times.truncate(self.pos + 1);
+            self.comments.truncate(self.pos + 1);
+            // playing on from an earlier position, so the clock goes back to it too
+            self.replay_clock();
+        }
         self.moves.push((Some(Move {x, y, face}), board, (red_inv, black_inv)));
+        self.times.push(self.clock.as_mut().map(|clock| clock.finish_turn(color)));
+        self.comments.push(Vec::new());
         self.pos += 1;
     }
 
+    /// Checks `mv` from the current position like `play`, without playing it.
+    pub fn check(&self, mv: Move) -> Result<(), MoveError> {
+        if self.is_over() {
+            return Err(MoveError::GameOver);
+        }
+        let (_, board, (red_inv, black_inv)) = self.get_move();
+        let inv = if board.color == Color::Red {red_inv} else {black_inv};
+        if !inv.can_place_face(mv.face) {
+            return Err(MoveError::NoPieces);
+        }
+        return board.check_tile(mv.x, mv.y, mv.face).map_err(MoveError::Tile);
+    }
+
+    /// Plays `mv` from the current position, checking it against the board and
+    /// the inventory of the player to move.
+    pub fn play(&mut self, mv: Move) -> Result<(), MoveError> {
+        self.check(mv)?;
+        let (_, board, (red_inv, black_inv)) = self.get_move();
+        let mut board = board.clone();
+        let mut red_inv = red_inv.clone();
+        let mut black_inv = black_inv.clone();
+
+        let inv = if board.color == Color::Red {&mut red_inv} else {&mut black_inv};
+        board.place_tile(mv.x, mv.y, mv.face).map_err(MoveError::Tile)?;
+        inv.place_face(mv.face);
+
+        self.add_move(mv.x, mv.y, mv.face, board, red_inv, black_inv);
+        return Ok(());
+    }
+
+    /// Plays `mv` after the last move, leaving the position being looked at alone
+    /// unless it was the last one.
+    pub fn play_live(&mut self, mv: Move) -> Result<(), MoveError> {
+        let pos = self.pos;
+        let live = pos + 1 == self.moves.len();
+        self.pos = self.moves.len() - 1;
+        let result = self.play(mv);
+        if !live {
+            self.pos = pos;
+        }
+        return result;
+    }
+
     pub fn get_move(&self) -> &(Option<Move>, Board, (Inventory, Inventory)) {
         return &self.moves[self.pos];
     }
@@ -52,13 +151,566 @@ impl Game {
         return &mut self.moves[self.pos];
     }
 
+    pub fn get_history(&self) -> &[(Option<Move>, Board, (Inventory, Inventory))] {
+        return &self.moves;
+    }
+
     pub fn get_pos(&self) -> usize {
         return self.pos;
     }
+
+    pub fn get_clock(&self) -> Option<&Clock> {
+        return self.clock.as_ref();
+    }
+
+    /// Time spent on each move of the history, if the game has a clock.
+    pub fn get_times(&self) -> &[Option<Duration>] {
+        return &self.times;
+    }
+
+    /// Runs the clock of the player to move at the end of the history.
+    pub fn tick(&mut self, delta: Duration) {
+        if self.is_over() {
+            return;
+        }
+        let color = self.moves.last().unwrap().1.color;
+        if let Some(clock) = &mut self.clock {
+            clock.tick(color, delta);
+        }
+    }
+
+    /// The game is over once neither player has a piece left to place,
+    /// a player runs out of time, or it is ended early.
+    pub fn is_over(&self) -> bool {
+        return self.get_result().is_some();
+    }
+
+    pub fn get_result(&self) -> Option<GameResult> {
+        if self.result.is_some() {
+            return self.result;
+        }
+        if let Some(color) = self.clock.as_ref().and_then(|clock| clock.flagged) {
+            return Some(GameResult {winner: Some(color.opponent()), reason: EndReason::Timeout});
+        }
+        let (_, board, (red_inv, black_inv)) = self.moves.last().unwrap();
+        if red_inv.is_empty() && black_inv.is_empty() {
+            return Some(GameResult {winner: board.get_winner(), reason: EndReason::Lines});
+        }
+        return None;
+    }
+
+    /// Ends the game before the board decides it, e.g. by resignation.
+    pub fn end(&mut self, result: GameResult) {
+        if !self.is_over() {
+            self.result = Some(result);
+        }
+    }
+
+    /// Applies an offer `from` made that the other player accepted.
+    /// A takeback undoes `from`'s last move, and the opponent's reply if there was one.
+    pub fn accept(&mut self, offer: Offer, from: Color) -> bool {
+        if self.is_over() {
+            return false;
+        }
+        match offer {
+            Offer::Draw => self.end(GameResult {winner: None, reason: EndReason::Agreement}),
+            Offer::Takeback => {
+                let to_move = self.moves.last().unwrap().1.color;
+                let plies = if to_move == from {2} else {1};
+                if self.moves.len() <= plies {
+                    return false;
+                }
+                self.moves.truncate(self.moves.len() - plies);
+                self.times.truncate(self.moves.len());
+                self.keep_comments();
+                self.pos = self.moves.len() - 1;
+                self.replay_clock();
+            }
+        }
+        return true;
+    }
+
+    /// Attaches `comment` to the move being played, the one after the last position.
+    pub fn comment(&mut self, comment: String) {
+        // a comment is one line of the record
+        self.comments.last_mut().unwrap().push(comment.replace(|c: char| c.is_control(), " "));
+    }
+
+    /// The comments on the move played from each position of the history.
+    pub fn get_comments(&self) -> &[Vec<String>] {
+        return &self.comments;
+    }
+
+    /// Takes over the comments of `other` for the moves both games share, e.g. when
+    /// the history is replaced by one sent over the network.
+    pub fn copy_comments(&mut self, other: &Game) {
+        let shared = self.moves.iter().zip(&other.moves).take_while(|(a, b)| a.0 == b.0).count();
+        self.comments[..shared].clone_from_slice(&other.comments[..shared]);
+        if shared == self.moves.len() {
+            self.comments[shared - 1].extend(other.comments[shared..].iter().flatten().cloned());
+        }
+    }
+
+    /// Moves the comments on moves that were taken back onto the move now being played.
+    fn keep_comments(&mut self) {
+        let taken_back: Vec<String> = self.comments.drain(self.moves.len()..).flatten().collect();
+        self.comments.last_mut().unwrap().extend(taken_back);
+    }
+
+    fn replay_clock(&mut self) {
+        if let Some(clock) = &self.clock {
+            let times = self.moves.iter()
+                .zip(self.times.iter().skip(1))
+                .filter_map(|((_, board, _), time)| Some((board.color, (*time)?)));
+            self.clock = Some(Clock::replay(clock.control, times));
+        }
+    }
+
+    /// Writes the game as a record: the ruleset and the time control, if any,
+    /// on the first line, then `position <position>` if the game did not start
+    /// from an empty board, followed by one move per line in `Move` notation
+    /// with the seconds spent on it, e.g. `Kd4 12.5`.  Comments on a move come
+    /// before it, one per line starting with `;`.  A finished game ends with
+    /// its result, e.g. `result red resignation` or `result draw agreement`.
+    pub fn to_record(&self) -> String {
+        let mut record = self.ruleset.to_string();
+        if let Some(clock) = &self.clock {
+            record += &format!(" {}", clock.control);
+        }
+        record += "\n";
+        let start = self.get_start().to_string();
+        if start != Game::new(self.ruleset).get_start().to_string() {
+            record += &format!("position {start}\n");
+        }
+        for (i, ((mv, ..), time)) in self.moves.iter().zip(&self.times).enumerate().skip(1) {
+            for comment in &self.comments[i - 1] {
+                record += &format!("; {comment}\n");
+            }
+            match time {
+                Some(time) => record += &format!("{} {:.3}\n", mv.unwrap(), time.as_secs_f64()),
+                None => record += &format!("{}\n", mv.unwrap())
+            }
+        }
+        for comment in self.comments.last().unwrap() {
+            record += &format!("; {comment}\n");
+        }
+        if let Some(result) = self.get_result() {
+            record += &format!("result {result}\n");
+        }
+        return record;
+    }
+
+    /// Replays a record written by `to_record`, checking every move.
+    pub fn from_record(record: &str) -> Result<Game, RecordError> {
+        let mut lines = record.lines()
+            .enumerate()
+            .map(|(i, line)| (i + 1, line.trim()))
+            .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'));
+
+        let (_, header) = lines.next().ok_or(RecordError::Header)?;
+        let (ruleset, control) = header.split_once(' ').unwrap_or((header, ""));
+        let ruleset = ruleset.parse().map_err(|_| RecordError::Header)?;
+
+        let mut game = match control.trim() {
+            "" => Game::new(ruleset),
+            control => Game::with_clock(ruleset, control.parse().map_err(|_| RecordError::Header)?)
+        };
+        for (line_num, line) in lines {
+            if let Some(position) = line.strip_prefix("position ") {
+                if game.moves.len() > 1 {
+                    return Err(RecordError::Position(line_num));
+                }
+                game.set_start(position.parse().map_err(|_| RecordError::Position(line_num))?);
+                continue;
+            }
+            if let Some(comment) = line.strip_prefix(';') {
+                game.comment(comment.trim().to_string());
+                continue;
+            }
+            if let Some(result) = line.strip_prefix("result ") {
+                game.end(result.parse().map_err(|_| RecordError::Result(line_num))?);
+                continue;
+            }
+            let (mv, time) = line.split_once(' ').unwrap_or((line, ""));
+            let mv = mv.parse().map_err(|e| RecordError::Move(line_num, e))?;
+            if let (Some(clock), time) = (&mut game.clock, time.trim()) {
+                let time = time.parse().ok()
+                    .and_then(|secs| Duration::try_from_secs_f64(secs).ok())
+                    .ok_or(RecordError::Time(line_num))?;
+                clock.set_turn(time);
+            }
+            game.play(mv).map_err(|e| RecordError::Move(line_num, e))?;
+        }
+        return Ok(game);
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Game, RecordError> {
+        return Game::from_record(&fs::read_to_string(path).map_err(RecordError::Io)?);
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        return fs::write(path, self.to_record());
+    }
 }
 
+/// Loads every game record in a directory, paired with the path it was read from.
+pub fn load_records(dir: impl AsRef<Path>) -> io::Result<Vec<(PathBuf, Result<Game, RecordError>)>> {
+    let mut games = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_file() {
+            let game = Game::load(&path);
+            games.push((path, game));
+        }
+    }
+    games.sort_by(|a, b| a.0.cmp(&b.0));
+    return Ok(games);
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub struct Move {
-    x: u32,
-    y: u32,
-    face: Face
-}
\ No newline at end of file
+    pub x: u32,
+    pub y: u32,
+    pub face: Face
+}
+
+/// `Move` notation is the face letter, the column as a letter starting at `a`
+/// and the row as a number starting at `1` from the bottom, e.g. `Kd4`.
+impl fmt::Display for Move {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}{}", self.face.to_char(), (b'a' + self.x as u8) as char, self.y + 1)
+    }
+}
+
+impl FromStr for Move {
+    type Err = MoveError;
+
+    fn from_str(s: &str) -> Result<Move, MoveError> {
+        let mut chars = s.chars();
+        let face = chars.next()
+            .and_then(Face::from_char)
+            .ok_or(MoveError::Notation)?;
+        let x = chars.next()
+            .filter(|c| c.is_ascii_lowercase())
+            .ok_or(MoveError::Notation)? as u32 - 'a' as u32;
+        let y = chars.as_str().parse::<u32>()
+            .ok()
+            .filter(|y| *y > 0)
+            .ok_or(MoveError::Notation)? - 1;
+        return Ok(Move {x, y, face});
+    }
+}
+
+#[derive(Debug)]
+pub enum MoveError {
+    Notation,
+    /// The player has no pieces of that face left.
+    NoPieces,
+    Tile(TileError),
+    /// The game is already over.
+    GameOver,
+    /// It is someone else's move.
+    WrongTurn
+}
+
+impl MoveError {
+    /// Why the move cannot be played, for players to read.
+    pub fn describe(&self) -> String {
+        return match self {
+            MoveError::Notation => "that is not a move",
+            MoveError::NoPieces => "there are no pieces of that face left",
+            MoveError::Tile(TileError::BlockedTile) => "the square is taken or blocked",
+            MoveError::Tile(TileError::NonDescending) => "even a free face has to go next to a card",
+            MoveError::Tile(TileError::NotFree) => "it has to go next to a higher card while there is room for one",
+            MoveError::Tile(_) => "the square is off the board",
+            MoveError::GameOver => "the game is over",
+            MoveError::WrongTurn => "it is not your turn"
+        }.to_string();
+    }
+}
+
+#[derive(Debug)]
+pub enum RecordError {
+    Io(io::Error),
+    Header,
+    Move(usize, MoveError),
+    /// A move in a game with a clock without the time spent on it.
+    Time(usize),
+    Result(usize),
+    /// A position that cannot be read, or comes after the first move.
+    Position(usize)
+}
+
+/// How many of each face both players start with.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Variant {
+    /// Three of each.
+    #[default]
+    Standard,
+    /// Two of each.
+    Quick,
+    /// Four of each.
+    Long
+}
+
+impl Variant {
+    pub fn cards(self) -> u32 {
+        return match self {
+            Variant::Standard => 3,
+            Variant::Quick => 2,
+            Variant::Long => 4
+        };
+    }
+}
+
+impl FromStr for Variant {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Variant, ()> {
+        return match s {
+            "standard" => Ok(Variant::Standard),
+            "quick" => Ok(Variant::Quick),
+            "long" => Ok(Variant::Long),
+            _ => Err(())
+        };
+    }
+}
+
+/// The board size and variant a game is played with.  Written as the size, e.g. `7x7`,
+/// followed by the variant if it is not the standard one, e.g. `9x9-quick`.
+/// Both sides are odd, so the Wild is in the middle.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Ruleset {
+    pub size: (u32, u32),
+    pub variant: Variant
+}
+
+impl Default for Ruleset {
+    fn default() -> Ruleset {
+        Ruleset {size: (7, 7), variant: Variant::Standard}
+    }
+}
+
+impl fmt::Display for Ruleset {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (x, y) = self.size;
+        return match self.variant {
+            Variant::Standard => write!(f, "{x}x{y}"),
+            Variant::Quick => write!(f, "{x}x{y}-quick"),
+            Variant::Long => write!(f, "{x}x{y}-long")
+        };
+    }
+}
+
+impl FromStr for Ruleset {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Ruleset, ()> {
+        let (size, variant) = s.split_once('-').unwrap_or((s, "standard"));
+        return Ok(Ruleset {size: parse_size(size)?, variant: variant.parse()?});
+    }
+}
+
+/// Reads a board size such as `7x7`.
+pub fn parse_size(s: &str) -> Result<(u32, u32), ()> {
+    let (x, y) = s.split_once('x').ok_or(())?;
+    let side = |side: &str| side.parse::<u32>().ok()
+        .filter(|side| *side % 2 == 1 && (3..=MAX_BOARD_SIZE).contains(side))
+        .ok_or(());
+    return Ok((side(x)?, side(y)?));
+}
+
+/// A position on its own line: the rows from the top separated by `/`, the player
+/// to move and how many aces, kings, queens and jacks each player has left, e.g.
+/// `......./......./......./...W.../......./......./....... r 3333 3333`.
+/// Red's cards are upper case, Black's lower case and blocked squares are `#`.
+#[derive(Clone)]
+pub struct Position {
+    pub board: Board,
+    pub red: Inventory,
+    pub black: Inventory
+}
+
+impl fmt::Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (size_x, size_y) = self.board.get_size();
+        let rows: Vec<String> = (0..size_y).rev()
+            .map(|y| (0..size_x).map(|x| match self.board.get_tile(x, y).unwrap() {
+                Tile::None => '.',
+                Tile::Blocked => '#',
+                Tile::Card(face, Color::Black) => face.to_char().to_ascii_lowercase(),
+                Tile::Card(face, _) => face.to_char()
+            }).collect())
+            .collect();
+        let to_move = if self.board.color == Color::Black {'b'} else {'r'};
+        let counts = |inv: &Inventory| -> String {
+            [Face::Ace, Face::King, Face::Queen, Face::Jack].iter().map(|face| inv.get_num_face(*face).to_string()).collect()
+        };
+        return write!(f, "{} {to_move} {} {}", rows.join("/"), counts(&self.red), counts(&self.black));
+    }
+}
+
+impl FromStr for Position {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Position, ()> {
+        let parts: Vec<&str> = s.split_whitespace().collect();
+        let [rows, to_move, red, black] = parts[..] else {
+            return Err(());
+        };
+        let rows: Vec<&str> = rows.split('/').collect();
+        let size = parse_size(&format!("{}x{}", rows[0].len(), rows.len()))?;
+        if rows.iter().any(|row| row.len() != rows[0].len()) {
+            return Err(());
+        }
+
+        let mut board = Board::new(size.0, size.1);
+        let wild = board.get_tile(size.0 / 2, size.1 / 2).unwrap();
+        for (row, y) in rows.iter().zip((0..size.1).rev()) {
+            for (c, x) in row.chars().zip(0..size.0) {
+                let tile = match c {
+                    '.' => Tile::None,
+                    '#' => Tile::Blocked,
+                    'W' => Tile::Card(Face::Wild, Color::Both),
+                    c => Tile::Card(Face::from_char(c).ok_or(())?, if c.is_ascii_lowercase() {Color::Black} else {Color::Red})
+                };
+                // the Wild is always in the middle, and only there
+                if (tile == wild) != ((x, y) == (size.0 / 2, size.1 / 2)) {
+                    return Err(());
+                }
+                board.set_tile(x, y, tile);
+            }
+        }
+        board.color = match to_move {
+            "r" => Color::Red,
+            "b" => Color::Black,
+            _ => return Err(())
+        };
+
+        let inventory = |counts: &str| -> Result<Inventory, ()> {
+            let counts: Vec<u32> = counts.chars().map(|c| c.to_digit(10)).collect::<Option<_>>().ok_or(())?;
+            let [aces, kings, queens, jacks] = counts[..] else {
+                return Err(());
+            };
+            return Ok(Inventory::from_counts(aces, kings, queens, jacks));
+        };
+        return Ok(Position {board, red: inventory(red)?, black: inventory(black)?});
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum EndReason {
+    /// Nobody has any pieces left and the lines decide.
+    Lines,
+    Resignation,
+    /// The players agreed to a draw.
+    Agreement,
+    /// A player ran out of time.
+    Timeout,
+    /// A player left and did not come back in time.
+    Forfeit
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GameResult {
+    /// Nobody for a draw.
+    pub winner: Option<Color>,
+    pub reason: EndReason
+}
+
+impl GameResult {
+    /// The result for players to read, e.g. `Red wins by resignation`.
+    pub fn describe(&self) -> String {
+        let reason = match self.reason {
+            EndReason::Lines => "on lines",
+            EndReason::Resignation => "by resignation",
+            EndReason::Agreement => "by agreement",
+            EndReason::Timeout => "on time",
+            EndReason::Forfeit => "by forfeit"
+        };
+        return match self.winner {
+            Some(Color::Black) => format!("Black wins {reason}"),
+            Some(_) => format!("Red wins {reason}"),
+            None => format!("Draw {reason}")
+        };
+    }
+}
+
+/// Written as the winner, or `draw`, and the reason, e.g. `red resignation`.
+impl fmt::Display for GameResult {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let winner = match self.winner {
+            Some(Color::Black) => "black",
+            Some(_) => "red",
+            None => "draw"
+        };
+        let reason = match self.reason {
+            EndReason::Lines => "lines",
+            EndReason::Resignation => "resignation",
+            EndReason::Agreement => "agreement",
+            EndReason::Timeout => "timeout",
+            EndReason::Forfeit => "forfeit"
+        };
+        return write!(f, "{winner} {reason}");
+    }
+}
+
+impl FromStr for GameResult {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<GameResult, ()> {
+        let (winner, reason) = s.trim().split_once(' ').ok_or(())?;
+        let winner = match winner {
+            "red" => Some(Color::Red),
+            "black" => Some(Color::Black),
+            "draw" => None,
+            _ => return Err(())
+        };
+        let reason = match reason.trim() {
+            "lines" => EndReason::Lines,
+            "resignation" => EndReason::Resignation,
+            "agreement" => EndReason::Agreement,
+            "timeout" => EndReason::Timeout,
+            "forfeit" => EndReason::Forfeit,
+            _ => return Err(())
+        };
+        return Ok(GameResult {winner, reason});
+    }
+}
+
+/// Something a player asks of their opponent, who has to accept it.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Offer {
+    Draw,
+    Takeback
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mv(notation: &str) -> Move {
+        return notation.parse().unwrap();
+    }
+
+    #[test]
+    fn moves_after_a_result_are_refused() {
+        let mut game = Game::new(Ruleset::default());
+        game.play(mv("Kd3")).unwrap();
+        game.end(GameResult {winner: Some(Color::Red), reason: EndReason::Resignation});
+        assert!(matches!(game.check(mv("Qe4")), Err(MoveError::GameOver)));
+        assert!(matches!(game.play(mv("Qe4")), Err(MoveError::GameOver)));
+        assert_eq!(game.get_history().len(), 2);
+    }
+
+    #[test]
+    fn records_with_moves_after_a_result_are_refused() {
+        let header = Game::new(Ruleset::default()).to_record();
+        let ended = format!("{header}Kd3\nresult red resignation\n");
+        assert!(Game::from_record(&ended).unwrap().is_over());
+        let played_on = format!("{ended}Qe4\n");
+        assert!(matches!(Game::from_record(&played_on), Err(RecordError::Move(_, MoveError::GameOver))));
+    }
+}
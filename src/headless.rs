@@ -0,0 +1,184 @@
+use std::{
+    io::BufRead,
+    sync::{mpsc::{channel, Receiver, TryRecvError}, Mutex},
+    thread,
+    time::Duration
+};
+
+use bevy::{
+    app::{AppExit, ScheduleRunnerPlugin},
+    input::{keyboard::{Key, KeyboardInput}, ButtonState, InputPlugin},
+    prelude::*
+};
+
+use crate::{
+    board::{resize_board_system, spawn_board, spawn_game, update_screen},
+    bot::{bot_system, Computer, Player, Players},
+    engine::engine_system,
+    events::{add_events, apply_system},
+    game::{Game, Move},
+    keyboard::keyboard_system,
+    lines::LineOverlay,
+    mouse::{face_click, mouse_click_system, square_click, Click},
+    offer::{offer_system, PendingOffer},
+    status::StatusBar,
+    tile::Color,
+    Layout,
+    Theme
+};
+
+/// Input for a game without a window, one action per line: a move in `Move`
+/// notation, e.g. `Kd3`, played by clicking the face and then the square, or
+/// `press <key>`, e.g. `press P`.  Blank lines and lines starting with `#` are skipped.
+#[derive(Resource)]
+pub struct Script {
+    lines: Mutex<Receiver<String>>,
+    line_num: usize,
+    /// The move sent last, to check it was played, with its line and how long the game was before it.
+    sent: Option<(usize, Move, usize)>,
+    /// Frames left until injected key presses have been handled.
+    wait: u32
+}
+
+impl Script {
+    /// Reads the script from `input` in the background, so it can come from stdin as it is typed.
+    pub fn new(input: impl BufRead + Send + 'static) -> Script {
+        let (sender, receiver) = channel();
+        thread::spawn(move || {
+            for line in input.lines() {
+                let Ok(line) = line else {
+                    return;
+                };
+                if sender.send(line).is_err() {
+                    return;
+                }
+            }
+        });
+        return Script {lines: Mutex::new(receiver), line_num: 0, sent: None, wait: 0};
+    }
+}
+
+/// An app without a window that plays `game` through the same systems as the
+/// windowed one, with its input coming from `script`.  Once the script runs out
+/// and no bot or engine is to move, the game's record is printed and the app exits.
+pub fn headless_app(game: Game, players: Players, computer: Computer, script: Script) -> App {
+    let mut app = App::new();
+    app.add_plugins((
+        MinimalPlugins.set(ScheduleRunnerPlugin::run_loop(Duration::from_millis(1))),
+        AssetPlugin::default(),
+        InputPlugin
+    ));
+    app.init_asset::<Mesh>()
+        .init_asset::<ColorMaterial>()
+        .add_event::<Click>()
+        .init_resource::<PendingOffer>()
+        .init_resource::<Theme>()
+        .init_resource::<LineOverlay>()
+        .init_resource::<StatusBar>()
+        .insert_resource(players)
+        .insert_resource(script);
+    add_events(&mut app);
+    computer.insert(&mut app);
+    app.add_systems(Startup, move |mut commands: Commands, mut meshes: ResMut<Assets<Mesh>>, mut materials: ResMut<Assets<ColorMaterial>>, theme: Res<Theme>| {
+        spawn_board(&mut commands, &mut meshes, &mut materials, &theme, &game.get_move().1);
+        spawn_game(&mut commands, &game);
+    });
+    app.add_systems(Update, (
+        script_system, mouse_click_system, keyboard_system, offer_system, bot_system, engine_system,
+        apply_system, resize_board_system, update_screen
+    ).chain());
+    return app;
+}
+
+/// Sends the clicks and key presses for the next line of the script, once the
+/// last ones were handled and no bot or engine is to move.
+pub fn script_system(
+    mut script: ResMut<Script>,
+    players: Res<Players>,
+    layout: Res<Layout>,
+    game: Query<&Game>,
+    mut clicks: EventWriter<Click>,
+    mut keys: EventWriter<KeyboardInput>,
+    mut exit: EventWriter<AppExit>,
+) {
+    if script.wait > 0 {
+        script.wait -= 1;
+        return;
+    }
+    let g = game.single();
+    if let Some((line_num, mv, len)) = script.sent.take() {
+        if g.get_history().len() == len {
+            eprintln!("line {line_num}: {mv} was not played");
+        }
+    }
+    let (_, b, (red_inv, black_inv)) = g.get_move();
+    let live = g.get_pos() + 1 == g.get_history().len();
+    if live && !g.is_over() && players.get(b.color) != Player::Human {
+        return;
+    }
+
+    let line = match script.lines.lock().unwrap().try_recv() {
+        Ok(line) => line,
+        Err(TryRecvError::Empty) => return,
+        Err(TryRecvError::Disconnected) => {
+            print!("{}", g.to_record());
+            exit.send(AppExit);
+            return;
+        }
+    };
+    script.line_num += 1;
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+        return;
+    }
+
+    if let Some(key) = line.strip_prefix("press ") {
+        let Some(key_code) = key_code(key.trim()) else {
+            eprintln!("line {}: there is no {key} key", script.line_num);
+            return;
+        };
+        for state in [ButtonState::Pressed, ButtonState::Released] {
+            keys.send(KeyboardInput {
+                key_code,
+                logical_key: Key::Character(key.trim().into()),
+                state,
+                window: Entity::PLACEHOLDER
+            });
+        }
+        // the presses reach `ButtonInput` at the start of the next frame
+        script.wait = 1;
+        return;
+    }
+
+    let Ok(mv) = line.parse::<Move>() else {
+        eprintln!("line {}: {line} is not a move", script.line_num);
+        return;
+    };
+    // picking a face that is used up would leave the last one picked selected
+    let inv = if b.color == Color::Black {black_inv} else {red_inv};
+    if !inv.can_place_face(mv.face) {
+        eprintln!("line {}: {mv} was not played", script.line_num);
+        return;
+    }
+    clicks.send(Click(face_click(mv.face)));
+    clicks.send(Click(square_click(&layout, mv.x, mv.y)));
+    script.sent = Some((script.line_num, mv, g.get_history().len()));
+}
+
+/// The keys the game listens to.
+fn key_code(key: &str) -> Option<KeyCode> {
+    return match key.to_ascii_uppercase().as_str() {
+        "F" => Some(KeyCode::KeyF),
+        "P" => Some(KeyCode::KeyP),
+        "N" => Some(KeyCode::KeyN),
+        "L" => Some(KeyCode::KeyL),
+        "B" => Some(KeyCode::KeyB),
+        "R" => Some(KeyCode::KeyR),
+        "D" => Some(KeyCode::KeyD),
+        "T" => Some(KeyCode::KeyT),
+        "Y" => Some(KeyCode::KeyY),
+        "X" => Some(KeyCode::KeyX),
+        "V" => Some(KeyCode::KeyV),
+        _ => None
+    };
+}
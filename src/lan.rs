@@ -0,0 +1,230 @@
+use std::{
+    env,
+    io,
+    net::{IpAddr, Ipv4Addr, SocketAddr, UdpSocket},
+    sync::{mpsc::{channel, Receiver}, Arc, Mutex},
+    thread,
+    time::{Duration, Instant}
+};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    bot::Players,
+    net::{Network, NetUI},
+    tile::Color
+};
+
+/// Hosts announce their games to this UDP port on the local network.
+pub const DISCOVERY_PORT: u16 = 7877;
+const ANNOUNCE_INTERVAL: Duration = Duration::from_secs(1);
+/// A game that has not been announced for this long is taken off the list.
+const FORGET_AFTER: Duration = Duration::from_secs(3);
+const JOIN_KEYS: [KeyCode; 9] = [
+    KeyCode::Digit1, KeyCode::Digit2, KeyCode::Digit3,
+    KeyCode::Digit4, KeyCode::Digit5, KeyCode::Digit6,
+    KeyCode::Digit7, KeyCode::Digit8, KeyCode::Digit9
+];
+
+/// What a host says about its game, sent as one JSON datagram, e.g.
+/// `{"game":"descend","id":42,"name":"alice's game","port":7878,"ruleset":"7x7","open_seat":"black"}`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Announcement {
+    /// Always `descend`, so other traffic on the port is ignored.
+    pub game: String,
+    /// Tells games apart when the same one is heard from more than one address.
+    pub id: u64,
+    pub name: String,
+    /// The TCP port the game is hosted on, at the address the announcement came from.
+    pub port: u16,
+    pub ruleset: String,
+    pub open_seat: Option<Color>
+}
+
+impl Announcement {
+    pub fn new(id: u64, port: u16, ruleset: String, open_seat: Option<Color>) -> Announcement {
+        let user = env::var("USER").or_else(|_| env::var("USERNAME")).unwrap_or("someone".to_string());
+        Announcement {
+            game: "descend".to_string(),
+            id,
+            name: format!("{user}'s game"),
+            port,
+            ruleset,
+            open_seat
+        }
+    }
+}
+
+/// Broadcasts `announcement` every second, for as long as anyone holds on to it, from
+/// `address`, the one the game is hosted on.  It is also sent to the loopback address,
+/// so games hosted on this machine are found even where broadcasts are not looped back.
+/// A game hosted on the loopback address is only announced to this machine.
+pub fn announce(announcement: &Arc<Mutex<Announcement>>, address: IpAddr) -> io::Result<()> {
+    // announcements only go out over IPv4
+    let local = if address.is_unspecified() {IpAddr::V4(Ipv4Addr::UNSPECIFIED)} else {address};
+    let socket = UdpSocket::bind((local, 0))?;
+    let addresses = match address.is_loopback() {
+        true => vec![Ipv4Addr::LOCALHOST],
+        false => vec![Ipv4Addr::BROADCAST, Ipv4Addr::LOCALHOST]
+    };
+    socket.set_broadcast(true)?;
+    let announcement = Arc::downgrade(announcement);
+    thread::spawn(move || {
+        while let Some(announcement) = announcement.upgrade() {
+            let datagram = serde_json::to_vec(&*announcement.lock().unwrap()).unwrap();
+            drop(announcement);
+            for address in &addresses {
+                let _ = socket.send_to(&datagram, (*address, DISCOVERY_PORT));
+            }
+            thread::sleep(ANNOUNCE_INTERVAL);
+        }
+    });
+    return Ok(());
+}
+
+pub struct DiscoveredGame {
+    pub announcement: Announcement,
+    /// Where to connect to the game.
+    pub address: SocketAddr,
+    seen: Instant
+}
+
+/// Listens for hosts announcing their games while the player picks one to join.
+#[derive(Resource)]
+pub struct Discovery {
+    pub games: Vec<DiscoveredGame>,
+    announcements: Mutex<Receiver<(Announcement, SocketAddr)>>,
+    pub error: Option<String>
+}
+
+impl Discovery {
+    pub fn listen() -> io::Result<Discovery> {
+        let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, DISCOVERY_PORT))?;
+        let (sender, receiver) = channel();
+        thread::spawn(move || {
+            let mut buffer = [0; 1024];
+            while let Ok((len, from)) = socket.recv_from(&mut buffer) {
+                let Ok(announcement) = serde_json::from_slice::<Announcement>(&buffer[..len]) else {
+                    continue;
+                };
+                if announcement.game != "descend" {
+                    continue;
+                }
+                let address = SocketAddr::new(from.ip(), announcement.port);
+                if sender.send((announcement, address)).is_err() {
+                    return;
+                }
+            }
+        });
+
+        return Ok(Discovery {
+            games: Vec::new(),
+            announcements: Mutex::new(receiver),
+            error: None
+        });
+    }
+
+    /// Takes in the announcements received so far and forgets games that went quiet.
+    pub fn update(&mut self, now: Instant) {
+        let received: Vec<(Announcement, SocketAddr)> = self.announcements.lock().unwrap().try_iter().collect();
+        for (announcement, address) in received {
+            match self.games.iter_mut().find(|game| game.announcement.id == announcement.id) {
+                Some(game) => {
+                    // a game on this machine may only be listening on the loopback address
+                    if address.ip().is_loopback() {
+                        game.address = address;
+                    }
+                    game.announcement = announcement;
+                    game.seen = now;
+                },
+                None => self.games.push(DiscoveredGame {announcement, address, seen: now})
+            }
+        }
+        self.games.retain(|game| now.duration_since(game.seen) < FORGET_AFTER);
+    }
+}
+
+/// Keeps the list of games up to date and joins one when its number is pressed.
+pub fn discovery_system(
+    mut commands: Commands,
+    discovery: Option<ResMut<Discovery>>,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+) {
+    let Some(mut discovery) = discovery else {
+        return;
+    };
+    discovery.update(Instant::now());
+
+    let Some(i) = JOIN_KEYS.iter().position(|key| keyboard_input.just_pressed(*key)) else {
+        return;
+    };
+    let Some(game) = discovery.games.get(i) else {
+        return;
+    };
+    // take the open seat, or watch if both are taken
+    let address = game.address.to_string();
+    let network = match game.announcement.open_seat {
+        Some(_) => Network::connect(&address),
+        None => Network::watch(&address)
+    };
+    match network {
+        Ok(network) => {
+            commands.insert_resource::<Players>(network.players());
+            commands.insert_resource(network);
+            commands.remove_resource::<Discovery>();
+        },
+        Err(e) => discovery.error = Some(format!("could not join {address}: {e}"))
+    }
+}
+
+pub fn update_discovery_panel(
+    discovery: Option<Res<Discovery>>,
+    mut panel: Query<&mut Text, With<NetUI>>,
+) {
+    let Some(discovery) = discovery else {
+        return;
+    };
+    let mut value = "Games on the network, press a number to join:".to_string();
+    for (i, game) in discovery.games.iter().take(JOIN_KEYS.len()).enumerate() {
+        let seat = match game.announcement.open_seat {
+            Some(Color::Black) => "Black open",
+            Some(_) => "Red open",
+            None => "watch only"
+        };
+        value += &format!("\n{}. {} ({}, {seat}) at {}", i + 1, game.announcement.name, game.announcement.ruleset, game.address);
+    }
+    if discovery.games.is_empty() {
+        value += "\nlooking...";
+    }
+    if let Some(error) = &discovery.error {
+        value += &format!("\n{error}");
+    }
+    panel.single_mut().sections[0].value = value;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::random_id;
+
+    #[test]
+    fn announced_game_is_discovered() {
+        let mut discovery = Discovery::listen().unwrap();
+        let id = random_id();
+        let announcement = Arc::new(Mutex::new(Announcement::new(id, 7878, "7x7".to_string(), Some(Color::Black))));
+        announce(&announcement, Ipv4Addr::LOCALHOST.into()).unwrap();
+
+        let start = Instant::now();
+        let found = loop {
+            discovery.update(Instant::now());
+            if let Some(game) = discovery.games.iter().find(|game| game.announcement.id == id) {
+                break game;
+            }
+            assert!(start.elapsed() < Duration::from_secs(5), "timed out");
+            thread::sleep(Duration::from_millis(10));
+        };
+        assert_eq!(found.announcement, *announcement.lock().unwrap());
+        assert_eq!(found.address, SocketAddr::from((Ipv4Addr::LOCALHOST, 7878)));
+    }
+}
@@ -0,0 +1,144 @@
+pub mod board;
+pub mod book;
+pub mod bot;
+pub mod chat;
+pub mod clock;
+pub mod correspondence;
+pub mod engine;
+pub mod eval;
+pub mod events;
+pub mod game;
+pub mod headless;
+pub mod keyboard;
+pub mod lan;
+pub mod lines;
+pub mod menu;
+pub mod net;
+pub mod offer;
+pub mod options;
+pub mod plugin;
+pub mod preview;
+pub mod protocol;
+pub mod results;
+pub mod search;
+pub mod server;
+pub mod status;
+pub mod tile;
+pub mod tui;
+pub mod tune;
+pub mod mouse;
+
+use bevy::{ecs::{component::Component, system::Resource}, math::Vec2, render::color::Color as BevyColor};
+
+use tile::Color;
+
+pub const GAP: f32 = 1.15;
+
+/// Sizes and places on screen for a board of `size` squares.  The board's longer side is 600 pixels.
+#[derive(Resource, Clone, Copy)]
+pub struct Layout {
+    pub size: (u32, u32),
+    /// Distance between neighbouring squares.
+    pub spacing: f32,
+    pub card_size: f32,
+    pub block_size: f32,
+    pub font_size: f32
+}
+
+impl Layout {
+    pub fn new(size: (u32, u32)) -> Layout {
+        let spacing = 600. / size.0.max(size.1) as f32;
+        let card_size = spacing - GAP;
+        Layout {
+            size,
+            spacing,
+            card_size,
+            block_size: card_size * 0.27,
+            font_size: card_size * 8./9.
+        }
+    }
+
+    pub fn board_width(&self) -> f32 {
+        return self.spacing * self.size.0 as f32;
+    }
+
+    pub fn board_height(&self) -> f32 {
+        return self.spacing * self.size.1 as f32;
+    }
+
+    /// The centre of the square at `x`, `y`, with the first row at the bottom.
+    pub fn square_position(&self, x: u32, y: u32) -> Vec2 {
+        // distribute the squares from one edge of the board to the other
+        return Vec2::new(
+            -self.board_width() / 2. + x as f32 / (self.size.0 - 1) as f32 * self.board_width(),
+            -self.board_height() / 2. + y as f32 / (self.size.1 - 1) as f32 * self.board_height()
+        );
+    }
+}
+
+/// The colors the game is drawn in.
+#[derive(Resource, Clone, Copy)]
+pub struct Theme {
+    pub red: BevyColor,
+    pub black: BevyColor,
+    /// The Wild, which is both colors.
+    pub wild: BevyColor,
+    pub empty: BevyColor,
+    /// Empty squares the selected face can be placed on.
+    pub playable: BevyColor,
+    pub blocked: BevyColor,
+    /// Faces the player to move has none left of.
+    pub used_up: BevyColor,
+    /// The faces written on the cards.
+    pub card_text: BevyColor,
+    /// The panels around the board.
+    pub text: BevyColor,
+    pub button: BevyColor,
+    /// A button under the cursor.
+    pub button_hovered: BevyColor,
+    /// Behind the pause and game-over screens, over the board.
+    pub overlay: BevyColor,
+    /// The line that decided the game, or the lines of the length under the cursor.
+    pub highlight: BevyColor,
+    /// The lines drawn across Red's and Black's cards.
+    pub red_line: BevyColor,
+    pub black_line: BevyColor
+}
+
+impl Default for Theme {
+    fn default() -> Theme {
+        Theme {
+            red: BevyColor::rgba(1., 0., 0., 1.),
+            black: BevyColor::rgba(0.5, 0.5, 0.5, 1.),
+            wild: BevyColor::rgba(1., 0., 1., 1.),
+            empty: BevyColor::rgba(0., 0., 0., 1.),
+            playable: BevyColor::rgba(1., 1., 1., 1.),
+            blocked: BevyColor::rgba(1., 1., 0., 1.),
+            used_up: BevyColor::rgba(0.2, 0.2, 0.2, 1.),
+            card_text: BevyColor::rgba(0., 0., 0., 1.),
+            text: BevyColor::rgba(1., 1., 1., 1.),
+            button: BevyColor::rgba(0.2, 0.2, 0.2, 1.),
+            button_hovered: BevyColor::rgba(0.35, 0.35, 0.35, 1.),
+            overlay: BevyColor::rgba(0., 0., 0., 0.7),
+            highlight: BevyColor::rgba(1., 1., 0., 1.),
+            red_line: BevyColor::rgba(1., 0.8, 0.8, 0.8),
+            black_line: BevyColor::rgba(0.1, 0.1, 0.1, 0.8)
+        }
+    }
+}
+
+impl Theme {
+    pub fn color(&self, color: Color) -> BevyColor {
+        return match color {
+            Color::Red => self.red,
+            Color::Black => self.black,
+            Color::Both => self.wild
+        };
+    }
+}
+
+#[derive(Component)]
+pub struct InventoryUI;
+
+#[derive(Component)]
+pub struct LineUI(pub usize);
@@ -0,0 +1,81 @@
+use bevy::{prelude::*, sprite::{MaterialMesh2dBundle, Mesh2dHandle}};
+
+use crate::{board::Board, tile::Color, Layout, LineUI, Theme};
+
+/// What the lines drawn across the cards show.
+#[derive(Resource, Default)]
+pub struct LineOverlay {
+    /// Only each player's longest lines.
+    pub longest_only: bool,
+    /// The length of the lines to highlight, while its `LineUI` entry is under the cursor.
+    pub hovered: Option<u32>
+}
+
+#[derive(Component)]
+pub struct LineSegment;
+
+/// Keeps track of the `LineUI` entry under the cursor.
+pub fn line_hover_system(entries: Query<(&Interaction, &LineUI)>, mut overlay: ResMut<LineOverlay>) {
+    let hovered = entries.iter()
+        .find(|(interaction, _)| **interaction != Interaction::None)
+        .map(|(_, LineUI(i))| *i as u32 + 1);
+    if overlay.hovered != hovered {
+        overlay.hovered = hovered;
+    }
+}
+
+/// Draws both players' lines, the same ones `get_lines` counts, across the cards
+/// in the position being looked at, whenever it or the overlay changes.
+pub fn line_overlay_system(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    board: Query<Ref<Board>>,
+    overlay: Res<LineOverlay>,
+    layout: Res<Layout>,
+    theme: Res<Theme>,
+    segments: Query<Entity, With<LineSegment>>,
+) {
+    let b = board.single();
+    if !b.is_changed() && !overlay.is_changed() && !layout.is_changed() {
+        return;
+    }
+    for entity in &segments {
+        commands.entity(entity).despawn();
+    }
+    // the board is redrawn for its new size first
+    if b.get_size() != layout.size {
+        return;
+    }
+
+    let lines = b.get_line_segments();
+    let longest = |color| lines.iter().filter(|line| line.color == color).map(|line| line.len).max().unwrap_or(0);
+    let longest = [longest(Color::Black), longest(Color::Red)];
+    for line in &lines {
+        let highlighted = overlay.hovered == Some(line.len);
+        if overlay.longest_only && !highlighted && line.len < longest[(line.color == Color::Red) as usize] {
+            continue;
+        }
+
+        let steps = line.len as i32 - 1;
+        let start = layout.square_position(line.start.0, line.start.1);
+        let end = layout.square_position(
+            (line.start.0 as i32 + line.dir.0 * steps) as u32,
+            (line.start.1 as i32 + line.dir.1 * steps) as u32
+        );
+        let middle = (start + end) / 2.;
+        let angle = (end.y - start.y).atan2(end.x - start.x);
+        let color = match line.color {
+            _ if highlighted => theme.highlight,
+            Color::Black => theme.black_line,
+            _ => theme.red_line
+        };
+        // between the cards and their letters
+        commands.spawn((MaterialMesh2dBundle {
+            mesh: Mesh2dHandle(meshes.add(Rectangle::new(start.distance(end) + layout.block_size, layout.block_size * 0.5))),
+            material: materials.add(color),
+            transform: Transform::from_xyz(middle.x, middle.y, 0.5).with_rotation(Quat::from_rotation_z(angle)),
+            ..default()
+        }, LineSegment));
+    }
+}
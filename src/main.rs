@@ -1,255 +1,153 @@
-mod board;
-mod game;
-mod keyboard;
-mod tile;
-mod mouse;
+use std::{fs::File, io::{self, BufReader}, time::Duration};
 
-use bevy::{prelude::*, sprite::{MaterialMesh2dBundle, Mesh2dHandle}};
-use board::{update_screen, Board, Index, Inventory, LineCount};
-use game::Game;
-use keyboard::keyboard_system;
-use mouse::mouse_click_system;
-use tile::{Color, Face, Selected, Tile};
+use bevy::prelude::*;
+use descend::{
+    book::Book,
+    bot::{Computer, Player, Players},
+    engine::Engines,
+    clock::TimeControl,
+    correspondence::Correspondence,
+    eval::Evaluator,
+    headless::{headless_app, Script},
+    lan::Discovery,
+    net::{Network, DEFAULT_ADDRESS},
+    options::{FrontEnd, Options, USAGE},
+    plugin::DescendPlugin,
+    search::SearchConfig,
+    server::DEFAULT_FORFEIT_TIMEOUT,
+    tile::Color,
+    tui,
+    Theme
+};
 
-fn main() {
-    App::new()
-        .add_plugins(DefaultPlugins)
-        .add_systems(Startup, setup)
-        .add_systems(Update, (mouse_click_system, keyboard_system, update_screen).chain())
-        .run();
-}
-
-const GAP: f32 = 1.15;
-const SIZE_X: u32 = 7;
-const SIZE_Y: u32 = 7;
-const MIN_SIZE: u32 = [SIZE_X, SIZE_Y][(SIZE_X < SIZE_Y) as usize];
-const MAX_SIZE: u32 = [SIZE_X, SIZE_Y][(SIZE_X > SIZE_Y) as usize];
-const MIN_SPACING: f32 = 600. / MIN_SIZE as f32;
-const BOARD_WIDTH: f32 = MIN_SPACING * SIZE_X as f32;
-const BOARD_HEIGHT: f32 = MIN_SPACING * SIZE_Y as f32;
-const CARD_SIZE: f32 = 600. / MIN_SIZE as f32 - GAP;
-const BLOCK_SIZE: f32 = (600. / MIN_SIZE as f32 - GAP) * 0.27;
-const FONT_SIZE: f32 = CARD_SIZE as f32 * 8./9.;
+const BOOK_PATH: &str = "book.txt";
+const WEIGHTS_PATH: &str = "weights.txt";
+const SEARCH_PATH: &str = "search.txt";
+const KEY_PATH: &str = "correspondence.key";
+const CLOCK_PATH: &str = "clock.txt";
 
-fn setup(
-    mut commands: Commands,
-    mut meshes: ResMut<Assets<Mesh>>,
-    mut materials: ResMut<Assets<ColorMaterial>>,
-    _asset_server: Res<AssetServer>
-) {
-    let board = Board::new(SIZE_X, SIZE_Y);
-    let red_text_style = TextStyle {
-        font: default(),
-        font_size: 60.,
-        color: bevy::render::color::Color::rgba(1., 0., 0., 1.)
-    };
-    let red_line_text_style = TextStyle {
-        font: default(),
-        font_size: 30.,
-        color: bevy::render::color::Color::rgba(1., 0., 0., 1.)
-    };
-    let black_text_style = TextStyle {
-        font: default(),
-        font_size: 60.,
-        color: bevy::render::color::Color::rgba(0.5, 0.5, 0.5, 1.)
-    };
-    let black_line_text_style = TextStyle {
-        font: default(),
-        font_size: 30.,
-        color: bevy::render::color::Color::rgba(0.5, 0.5, 0.5, 1.)
+fn main() {
+    let options = match Options::parse(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("{e:?}\n{USAGE}");
+            return;
+        }
     };
-    let card_text_style = TextStyle {
-        font: default(),
-        font_size: 60.,
-        color: bevy::render::color::Color::rgba(0., 0., 0., 1.)
+    let clock = TimeControl::load(CLOCK_PATH).ok();
+    let game = match options.game(clock) {
+        Ok(game) => game,
+        Err(e) => {
+            eprintln!("could not load the game: {e:?}");
+            return;
+        }
     };
-    let tile_text_style = TextStyle {
-        font: default(),
-        font_size: FONT_SIZE,
-        color: bevy::render::color::Color::rgba(0., 0., 0., 1.)
+    let computer = match Engines::start(&options.engines) {
+        Ok(engines) => Computer {
+            book: Book::load(BOOK_PATH).unwrap_or_default(),
+            evaluator: Evaluator::load(WEIGHTS_PATH).unwrap_or_default(),
+            config: SearchConfig::load(SEARCH_PATH).unwrap_or_default(),
+            engines
+        },
+        Err(e) => {
+            eprintln!("could not start the engine: {e}");
+            return;
+        }
     };
-    
-    commands.spawn(Camera2dBundle::default());
 
-    for i in 0..(SIZE_X * SIZE_Y) as usize {
-        let x = i % SIZE_X as usize;
-        let y = i / SIZE_X as usize;
+    let args = &options.mode;
+    let address = args.get(1).map_or(DEFAULT_ADDRESS, String::as_str);
+    let ruleset = game.get_ruleset();
+    let network = match (args.first().map(String::as_str), args.get(2), args.get(3)) {
+        (None, ..) => None,
+        (Some("host"), None, _) => Some(Network::host(address, DEFAULT_FORFEIT_TIMEOUT, ruleset)),
+        (Some("host"), Some(secs), _) if secs.parse::<u64>().is_ok() => {
+            Some(Network::host(address, Duration::from_secs(secs.parse().unwrap()), ruleset))
+        },
+        (Some("connect"), ..) => Some(Network::connect(address)),
+        (Some("watch"), ..) => Some(Network::watch(address)),
+        (Some("join" | "tui"), None, _) if args.len() == 1 => None,
+        (Some("headless"), None, _) => None,
+        (Some("correspond"), Some(_), game_id) if matches!(address, "red" | "black") && args.len() <= 4
+            && game_id.is_none_or(|game_id| u64::from_str_radix(game_id, 16).is_ok()) => None,
+        (Some("resume"), Some(game_id), Some(token)) if u64::from_str_radix(game_id, 16).is_ok() => {
+            Some(Network::resume(address, u64::from_str_radix(game_id, 16).unwrap(), token))
+        },
+        (Some(_), ..) => {
+            eprintln!("{USAGE}");
+            return;
+        }
+    };
 
-        let color = match board.get_tile(x as u32, y as u32).ok().unwrap() {
-            Tile::None => bevy::render::color::Color::rgba(0., 0., 0., 1.),
-            Tile::Blocked => bevy::render::color::Color::rgba(1., 1., 0., 1.0),
-            Tile::Card(_, Color::Black) => bevy::render::color::Color::rgba(0.5, 0.5, 0.5, 1.0),
-            Tile::Card(_, Color::Red) => bevy::render::color::Color::rgba(1., 0., 0., 1.),
-            Tile::Card(_, Color::Both) => bevy::render::color::Color::rgba(1., 0., 1., 1.)
-        };
-        
-        let shape = match board.get_tile(x as u32, y as u32).ok().unwrap() {
-            Tile::None => Mesh2dHandle(meshes.add(Rectangle::new(CARD_SIZE, CARD_SIZE))),
-            Tile::Blocked => Mesh2dHandle(meshes.add(Circle::new(BLOCK_SIZE))),
-            Tile::Card(..) => Mesh2dHandle(meshes.add(Rectangle::new(CARD_SIZE, CARD_SIZE)))
+    // the terminal front-end needs no window
+    if options.front_end == FrontEnd::Terminal {
+        match tui::run(game, options.players, computer) {
+            Ok(game) if game.get_history().len() > 1 => print!("{}", game.to_record()),
+            Ok(_) => (),
+            Err(e) => eprintln!("could not play in the terminal: {e}")
+        }
+        return;
+    }
+    if args.first().is_some_and(|mode| mode == "headless") {
+        let script = match args.get(1) {
+            Some(path) => match File::open(path) {
+                Ok(file) => Script::new(BufReader::new(file)),
+                Err(e) => {
+                    eprintln!("could not open the script {path}: {e}");
+                    return;
+                }
+            },
+            None => Script::new(BufReader::new(io::stdin()))
         };
-
-        commands.spawn((MaterialMesh2dBundle {
-            mesh: shape,
-            material: materials.add(color),
-            transform: Transform::from_xyz(
-                // Distribute shapes from -X_EXTENT to +X_EXTENT.
-                -(MIN_SPACING * SIZE_X as f32) / 2. + x as f32 / (SIZE_X - 1) as f32 * (MIN_SPACING * SIZE_X as f32),
-                -(MIN_SPACING * SIZE_Y as f32) / 2. + y as f32 / (SIZE_Y - 1) as f32 * (MIN_SPACING * SIZE_Y as f32),
-                0.0,
-            ),
-            ..default()
-        }, Index(i as usize)));
-        commands.spawn((Text2dBundle {
-            text: Text::from_section(match board.get_tile(x as u32, y as u32).ok().unwrap() {
-                Tile::Card(Face::Wild, ..) => "W",
-                Tile::Card(Face::Ace, ..) => "A",
-                Tile::Card(Face::King, ..) => "K",
-                Tile::Card(Face::Queen, ..) => "Q",
-                Tile::Card(Face::Jack, ..) => "J",
-                _ => " "
-            }, tile_text_style.clone()),
-            transform: Transform::from_xyz(
-                // Distribute shapes from -X_EXTENT to +X_EXTENT.
-                -(MIN_SPACING * SIZE_X as f32) / 2. + x as f32 / (SIZE_X - 1) as f32 * (MIN_SPACING * SIZE_X as f32),
-                -(MIN_SPACING * SIZE_Y as f32) / 2. + y as f32 / (SIZE_Y - 1) as f32 * (MIN_SPACING * SIZE_Y as f32),
-                1.,
-            ),
-            ..default()
-        }, Index(i as usize)));
+        headless_app(game, options.players, computer, script).run();
+        return;
     }
 
-    commands.insert_resource(LineCount([[0; MAX_SIZE as usize]; 2]));
-
-    for i in 1..(MAX_SIZE as usize) {
-        commands.spawn((TextBundle::from_section(
-            format!("{}: 0", i + 1), 
-            red_line_text_style.clone()
-        ).with_style(Style {
-            position_type: PositionType::Absolute,
-            top: Val::Px(10. + (i - 1) as f32 * 20.),
-            right: Val::Percent(55.),
-            ..Default::default()
-        }), LineUI(i), Color::Red));
-        commands.spawn((TextBundle::from_section(
-            format!("{}: 0", i + 1), 
-            black_line_text_style.clone()
-        ).with_style(Style {
-            position_type: PositionType::Absolute,
-            top: Val::Px(10. + (i - 1) as f32 * 20.),
-            left: Val::Percent(55.),
-            ..Default::default()
-        }), LineUI(i), Color::Black));
-    }
-    
-    for i in 0..4 {
-        commands.spawn(MaterialMesh2dBundle {
-            mesh: Mesh2dHandle(meshes.add(Rectangle::new(75., 75.))),
-            material: materials.add(bevy::render::color::Color::rgba(1., 0., 0., 1.)),
-            transform: Transform::from_xyz(-500., 240. - (i * 160) as f32, 0.),
-            ..default()
-        });
-        commands.spawn((Text2dBundle {
-            text: Text::from_section(match i {
-                0 => "A",
-                1 => "K",
-                2 => "Q",
-                3 => "J",
-                _ => unreachable!()
-            }, card_text_style.clone()),
-            transform: Transform::from_xyz(-500., 240. - (i * 160) as f32, 1.),
-            ..default()
-        }, InventoryUI));
-    }
-    commands.spawn(
-        (Text2dBundle {
-            text: Text::from_section("A * * *", red_text_style.clone()),
-            text_anchor: bevy::sprite::Anchor::Center,
-            transform: Transform::from_xyz(500., 300., 0.),
-            ..default()
-        },
-        Color::Red, 
-        Face::Ace
-    ));
-    commands.spawn(
-        (Text2dBundle {
-            text: Text::from_section("K * * *", red_text_style.clone()),
-            text_anchor: bevy::sprite::Anchor::Center,
-            transform: Transform::from_xyz(500., 252., 0.),
-            ..default()
-        },
-        Color::Red, 
-        Face::King
-    ));
-    commands.spawn(
-        (Text2dBundle {
-            text: Text::from_section("Q * * *", red_text_style.clone()),
-            text_anchor: bevy::sprite::Anchor::Center,
-            transform: Transform::from_xyz(500., 204., 0.),
-            ..default()
-        },
-        Color::Red, 
-        Face::Queen
-    ));
-    commands.spawn(
-        (Text2dBundle {
-            text: Text::from_section("J * * *", red_text_style.clone()),
-            text_anchor: bevy::sprite::Anchor::Center,
-            transform: Transform::from_xyz(500., 156., 0.),
-            ..default()
+    let mut app = App::new();
+    let players = match network {
+        None if args.first().is_some_and(|mode| mode == "join") => match Discovery::listen() {
+            // nobody plays until a game is picked from the list
+            Ok(discovery) => {
+                app.insert_resource(discovery);
+                Players {red: Player::Remote, black: Player::Remote}
+            },
+            Err(e) => {
+                eprintln!("could not listen for games on the network: {e}");
+                return;
+            }
         },
-        Color::Red, 
-        Face::Jack
-    ));
-    commands.spawn(
-        (Text2dBundle {
-            text: Text::from_section("A * * *", black_text_style.clone()),
-            text_anchor: bevy::sprite::Anchor::Center,
-            transform: Transform::from_xyz(500., -156., 0.),
-            ..default()
+        None if args.first().is_some_and(|mode| mode == "correspond") => {
+            let color = if address == "red" {Color::Red} else {Color::Black};
+            let game_id = args.get(3).map(|game_id| u64::from_str_radix(game_id, 16).unwrap());
+            match Correspondence::open(color, &args[2], KEY_PATH, game_id) {
+                Ok(correspondence) => {
+                    app.insert_resource(correspondence);
+                    match color {
+                        Color::Red => Players {red: Player::Human, black: Player::Remote},
+                        _ => Players {red: Player::Remote, black: Player::Human}
+                    }
+                },
+                Err(e) => {
+                    eprintln!("could not open the correspondence game in {}: {e:?}", args[2]);
+                    return;
+                }
+            }
         },
-        Color::Black, 
-        Face::Ace
-    ));
-    commands.spawn(
-        (Text2dBundle {
-            text: Text::from_section("K * * *", black_text_style.clone()),
-            text_anchor: bevy::sprite::Anchor::Center,
-            transform: Transform::from_xyz(500., -204., 0.),
-            ..default()
+        None => options.players,
+        Some(Ok(network)) => {
+            let players = network.players();
+            app.insert_resource(network);
+            players
         },
-        Color::Black, 
-        Face::King
-    ));
-    commands.spawn(
-        (Text2dBundle {
-            text: Text::from_section("Q * * *", black_text_style.clone()),
-            text_anchor: bevy::sprite::Anchor::Center,
-            transform: Transform::from_xyz(500., -252., 0.),
-            ..default()
-        },
-        Color::Black, 
-        Face::Queen
-    ));
-    commands.spawn(
-        (Text2dBundle {
-            text: Text::from_section("J * * *", black_text_style.clone()),
-            text_anchor: bevy::sprite::Anchor::Center,
-            transform: Transform::from_xyz(500., -300., 0.),
-            ..default()
-        },
-        Color::Black, 
-        Face::Jack
-    ));
-    commands.spawn(board);
-    commands.spawn(Game::new(SIZE_X, SIZE_Y));
-    commands.spawn((Inventory::new(3), Color::Black));
-    commands.spawn((Inventory::new(3), Color::Red));
-    commands.spawn(Selected {face: None});
-}
-
-#[derive(Component)]
-pub struct InventoryUI;
+        Some(Err(e)) => {
+            eprintln!("could not open a connection on {address}: {e}");
+            return;
+        }
+    };
 
-#[derive(Component)]
-pub struct LineUI(pub usize);
\ No newline at end of file
+    // a plain launch opens on the main menu, set up with the options given
+    let start = (!args.is_empty() || options.start.is_some()).then_some(game);
+    computer.insert(&mut app);
+    app.add_plugins((DefaultPlugins, DescendPlugin {ruleset, players, theme: Theme::default(), clock, start}))
+        .run();
+}
@@ -0,0 +1,342 @@
+use bevy::{app::AppExit, prelude::*, render::color::Color as BevyColor};
+
+use crate::{
+    board::{spawn_game, Board, Inventory},
+    bot::{BotTask, Player, Players},
+    clock::TimeControl,
+    correspondence::Correspondence,
+    engine::Engines,
+    events::GameEnded,
+    game::{Game, Ruleset, Variant, MAX_BOARD_SIZE},
+    lan::Discovery,
+    net::Network,
+    offer::PendingOffer,
+    results::{save_record, spawn_line_table, SavedText},
+    tile::{Color, Selected},
+    Theme
+};
+
+#[derive(States, Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum AppState {
+    #[default]
+    MainMenu,
+    /// Picking the board size, the rules and who plays.
+    NewGameSetup,
+    InGame,
+    Paused,
+    GameOver,
+    /// Looking back through a finished game.
+    Analysis
+}
+
+/// The game the next one is started with, from the setup screen or a rematch.
+#[derive(Resource, Clone, Copy)]
+pub struct GameSetup {
+    pub ruleset: Ruleset,
+    pub players: Players,
+    pub clock: Option<TimeControl>
+}
+
+impl GameSetup {
+    pub fn game(&self) -> Game {
+        return match self.clock {
+            Some(control) => Game::with_clock(self.ruleset, control),
+            None => Game::new(self.ruleset)
+        };
+    }
+}
+
+/// The root of the screen shown in a state other than `InGame`, despawned when the state is left.
+#[derive(Component)]
+pub struct Screen;
+
+#[derive(Component, Clone, Copy, PartialEq)]
+pub enum MenuButton {
+    NewGame,
+    Quit,
+    Size,
+    Rules,
+    Player(Color),
+    Start,
+    Back,
+    Resume,
+    Rematch,
+    SaveRecord,
+    Analyze,
+    MainMenu
+}
+
+/// The result shown on the game-over screen.
+#[derive(Component)]
+pub struct ResultText;
+
+/// Whether the game goes on: while it is played, and while it is paused if someone
+/// elsewhere is playing, as they do not stop.
+pub fn game_running(state: Res<State<AppState>>, players: Res<Players>) -> bool {
+    return match state.get() {
+        AppState::InGame => true,
+        AppState::Paused => players.has_remote(),
+        _ => false
+    };
+}
+
+pub fn spawn_main_menu(mut commands: Commands, theme: Res<Theme>) {
+    spawn_screen(&mut commands, &theme, theme.empty, "Descend", &[MenuButton::NewGame, MenuButton::Quit]);
+}
+
+pub fn spawn_setup_screen(mut commands: Commands, theme: Res<Theme>) {
+    spawn_screen(&mut commands, &theme, theme.empty, "New Game", &[
+        MenuButton::Size,
+        MenuButton::Rules,
+        MenuButton::Player(Color::Red),
+        MenuButton::Player(Color::Black),
+        MenuButton::Start,
+        MenuButton::Back
+    ]);
+}
+
+pub fn spawn_pause_screen(mut commands: Commands, theme: Res<Theme>) {
+    spawn_screen(&mut commands, &theme, theme.overlay, "Paused", &[MenuButton::Resume, MenuButton::MainMenu]);
+}
+
+/// The result, the line lengths that decided it and what to do next.
+pub fn spawn_game_over_screen(mut commands: Commands, theme: Res<Theme>, players: Res<Players>, game: Query<&Game>) {
+    // nobody elsewhere has asked for a rematch
+    let buttons: &[MenuButton] = match players.has_remote() {
+        true => &[MenuButton::SaveRecord, MenuButton::Analyze, MenuButton::MainMenu],
+        false => &[MenuButton::Rematch, MenuButton::SaveRecord, MenuButton::Analyze, MenuButton::MainMenu]
+    };
+    let screen = spawn_screen(&mut commands, &theme, theme.overlay, "Game Over", buttons);
+    let result = commands.spawn((TextBundle::from_section("", TextStyle {
+        font: default(),
+        font_size: 30.,
+        color: theme.text
+    }).with_style(Style {
+        margin: UiRect::bottom(Val::Px(20.)),
+        ..Default::default()
+    }), ResultText)).id();
+    let table = spawn_line_table(&mut commands, &theme, game.single());
+    let saved = commands.spawn((TextBundle::from_section("", TextStyle {
+        font: default(),
+        font_size: 20.,
+        color: theme.text
+    }), SavedText)).id();
+    commands.entity(screen).insert_children(1, &[result, table]).add_child(saved);
+}
+
+/// A full-window column with `title` and `buttons`, labelled by `update_menu_labels`.
+fn spawn_screen(commands: &mut Commands, theme: &Theme, background: BevyColor, title: &str, buttons: &[MenuButton]) -> Entity {
+    return commands.spawn((NodeBundle {
+        style: Style {
+            position_type: PositionType::Absolute,
+            width: Val::Percent(100.),
+            height: Val::Percent(100.),
+            flex_direction: FlexDirection::Column,
+            align_items: AlignItems::Center,
+            justify_content: JustifyContent::Center,
+            ..Default::default()
+        },
+        background_color: background.into(),
+        z_index: ZIndex::Global(10),
+        ..default()
+    }, Screen)).with_children(|parent| {
+        parent.spawn(TextBundle::from_section(title, TextStyle {
+            font: default(),
+            font_size: 60.,
+            color: theme.text
+        }).with_style(Style {
+            margin: UiRect::bottom(Val::Px(30.)),
+            ..Default::default()
+        }));
+        for button in buttons {
+            parent.spawn((ButtonBundle {
+                style: Style {
+                    width: Val::Px(260.),
+                    height: Val::Px(50.),
+                    margin: UiRect::all(Val::Px(6.)),
+                    align_items: AlignItems::Center,
+                    justify_content: JustifyContent::Center,
+                    ..Default::default()
+                },
+                background_color: theme.button.into(),
+                ..default()
+            }, *button)).with_children(|parent| {
+                parent.spawn(TextBundle::from_section("", TextStyle {
+                    font: default(),
+                    font_size: 26.,
+                    color: theme.text
+                }));
+            });
+        }
+    }).id();
+}
+
+pub fn despawn_screen(mut commands: Commands, screens: Query<Entity, With<Screen>>) {
+    for entity in &screens {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+/// Writes each button's label, which for the setup screen shows what was picked.
+pub fn update_menu_labels(
+    setup: Res<GameSetup>,
+    game: Query<&Game>,
+    buttons: Query<(&MenuButton, &Children)>,
+    mut texts: Query<&mut Text, Without<ResultText>>,
+    mut result: Query<&mut Text, With<ResultText>>,
+) {
+    for (button, children) in &buttons {
+        let Some(mut text) = children.first().and_then(|child| texts.get_mut(*child).ok()) else {
+            continue;
+        };
+        let player = |color| match setup.players.get(color) {
+            Player::Human => "human",
+            Player::Bot => "bot",
+            Player::Engine => "engine",
+            Player::Remote => "remote"
+        };
+        text.sections[0].value = match button {
+            MenuButton::NewGame => "New Game".to_string(),
+            MenuButton::Quit => "Quit".to_string(),
+            MenuButton::Size => format!("Board: {}x{}", setup.ruleset.size.0, setup.ruleset.size.1),
+            MenuButton::Rules => format!("Rules: {}", match setup.ruleset.variant {
+                Variant::Standard => "standard",
+                Variant::Quick => "quick",
+                Variant::Long => "long"
+            }),
+            MenuButton::Player(Color::Black) => format!("Black: {}", player(Color::Black)),
+            MenuButton::Player(_) => format!("Red: {}", player(Color::Red)),
+            MenuButton::Start => "Start".to_string(),
+            MenuButton::Back => "Back".to_string(),
+            MenuButton::Resume => "Resume".to_string(),
+            MenuButton::Rematch => "Rematch, Colors Swapped".to_string(),
+            MenuButton::SaveRecord => "Save Record".to_string(),
+            MenuButton::Analyze => "Analyze".to_string(),
+            MenuButton::MainMenu => "Main Menu".to_string()
+        };
+    }
+    if let Ok(mut text) = result.get_single_mut() {
+        text.sections[0].value = game.single().get_result().map_or(String::new(), |result| result.describe());
+    }
+}
+
+pub fn menu_button_system(
+    theme: Res<Theme>,
+    mut next_state: ResMut<NextState<AppState>>,
+    mut setup: ResMut<GameSetup>,
+    mut engines: ResMut<Engines>,
+    players: Res<Players>,
+    game: Query<&Game>,
+    mut buttons: Query<(&Interaction, &MenuButton, &mut BackgroundColor), Changed<Interaction>>,
+    mut saved: Query<&mut Text, With<SavedText>>,
+    mut exit: EventWriter<AppExit>,
+) {
+    for (interaction, button, mut background) in &mut buttons {
+        *background = match interaction {
+            Interaction::None => theme.button,
+            _ => theme.button_hovered
+        }.into();
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        match button {
+            MenuButton::NewGame => next_state.set(AppState::NewGameSetup),
+            MenuButton::Quit => {
+                exit.send(AppExit);
+            },
+            MenuButton::Size => {
+                let side = setup.ruleset.size.0 + 2;
+                let side = if side > MAX_BOARD_SIZE {3} else {side};
+                setup.ruleset.size = (side, side);
+            },
+            MenuButton::Rules => setup.ruleset.variant = match setup.ruleset.variant {
+                Variant::Standard => Variant::Quick,
+                Variant::Quick => Variant::Long,
+                Variant::Long => Variant::Standard
+            },
+            MenuButton::Player(color) => {
+                // an engine can only play if one was started for that color
+                let player = match setup.players.get(*color) {
+                    Player::Human => Player::Bot,
+                    Player::Bot if engines.get_mut(*color).is_some() => Player::Engine,
+                    _ => Player::Human
+                };
+                setup.players.set(*color, player);
+            },
+            MenuButton::Rematch => {
+                setup.players = Players {red: players.black, black: players.red};
+                engines.swap();
+                next_state.set(AppState::InGame);
+            },
+            MenuButton::SaveRecord => {
+                let Ok(mut text) = saved.get_single_mut() else {
+                    continue;
+                };
+                text.sections[0].value = match save_record(game.single()) {
+                    Ok(path) => format!("saved to {}", path.display()),
+                    Err(e) => format!("could not save the record: {e}")
+                };
+            },
+            MenuButton::Analyze => next_state.set(AppState::Analysis),
+            MenuButton::Start | MenuButton::Resume => next_state.set(AppState::InGame),
+            MenuButton::Back | MenuButton::MainMenu => next_state.set(AppState::MainMenu)
+        }
+    }
+}
+
+/// Escape pauses and resumes the game, leaves the setup screen and ends the analysis of a
+/// finished game, Enter starts a new game.
+pub fn menu_key_system(
+    state: Res<State<AppState>>,
+    mut next_state: ResMut<NextState<AppState>>,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+) {
+    let escape = keyboard_input.just_pressed(KeyCode::Escape);
+    let enter = keyboard_input.just_pressed(KeyCode::Enter);
+    match state.get() {
+        AppState::MainMenu if enter => next_state.set(AppState::NewGameSetup),
+        AppState::NewGameSetup if enter => next_state.set(AppState::InGame),
+        AppState::NewGameSetup if escape => next_state.set(AppState::MainMenu),
+        AppState::InGame if escape => next_state.set(AppState::Paused),
+        AppState::Paused if escape => next_state.set(AppState::InGame),
+        AppState::Analysis if escape => next_state.set(AppState::GameOver),
+        _ => ()
+    }
+}
+
+/// Shows the game-over screen once the game is decided, however that happened.
+pub fn game_over_system(
+    mut ended: EventReader<GameEnded>,
+    state: Res<State<AppState>>,
+    mut next_state: ResMut<NextState<AppState>>,
+) {
+    if ended.read().count() > 0 && matches!(state.get(), AppState::InGame | AppState::Paused) {
+        next_state.set(AppState::GameOver);
+    }
+}
+
+/// Replaces the game with a new one set up by `GameSetup`.
+pub fn new_game_system(
+    mut commands: Commands,
+    setup: Res<GameSetup>,
+    mut players: ResMut<Players>,
+    mut bot_task: ResMut<BotTask>,
+    mut pending: ResMut<PendingOffer>,
+    old: Query<Entity, Or<(With<Game>, With<Board>, With<Inventory>, With<Selected>)>>,
+) {
+    for entity in &old {
+        commands.entity(entity).despawn();
+    }
+    spawn_game(&mut commands, &setup.game());
+    *players = setup.players;
+    *pending = PendingOffer::default();
+    bot_task.cancel();
+}
+
+/// Leaves a game for the main menu, hanging up on anyone elsewhere.
+pub fn leave_game_system(mut commands: Commands, mut bot_task: ResMut<BotTask>) {
+    commands.remove_resource::<Network>();
+    commands.remove_resource::<Discovery>();
+    commands.remove_resource::<Correspondence>();
+    bot_task.cancel();
+}
@@ -1,63 +1,124 @@
 use bevy::{prelude::*, window::PrimaryWindow};
 
-use crate::{board::{Board, Inventory, LineCount}, game::Game, tile::{Color, Face, Selected}, BOARD_HEIGHT, BOARD_WIDTH, CARD_SIZE, SIZE_X, SIZE_Y};
+use crate::{
+    board::{Board, Inventory},
+    bot::{Player, Players},
+    events::PlaceRequested,
+    game::{Game, Move, MoveError},
+    status::StatusBar,
+    tile::{Color, Face, Selected},
+    Layout
+};
 
-pub fn mouse_click_system(
+/// A left click, in pixels from the middle of the window with y pointing down.
+/// Clicks come from the mouse, or from a script when there is no window.
+#[derive(Event, Clone, Copy)]
+pub struct Click(pub Vec2);
+
+/// Turns left clicks in the window into `Click` events.
+pub fn cursor_system(
     window: Query<&Window, With<PrimaryWindow>>,
-    mut game: Query<&mut Game>,
-    mut board: Query<&mut Board>,
-    mut selected: Query<&mut Selected>,
-    mut inventory: Query<(&mut Inventory, &Color)>, 
-    mut line_counts: ResMut<LineCount>,
     mouse_button_input: Res<ButtonInput<MouseButton>>,
+    mut clicks: EventWriter<Click>,
 ) {
-    if mouse_button_input.just_pressed(MouseButton::Left) {
-        if let Some(pos) = window.single().cursor_position() {
-            let w = window.single().width() / 2.;
-            let h = window.single().height() / 2.;
-
-            if pos.x - w >= -537.5 && pos.x - w <= -462.5 {
-                let mut value = selected.single().face;
-                for i in 0..4 {
-                    let level = -240. + (i * 160) as f32; 
-                    if pos.y - h >= level - 37.5 && pos.y - h <= level + 37.5 {
-                        let face = Face::from_num(3 - i);
-                        let inv = inventory.iter().find(|e| *e.1 == board.single().color).unwrap().0;
-                        if inv.can_place_face(face) {
-                            value = Some(Face::from_num(3 - i));
-                        }
-                        break;
-                    }
-                }
-                selected.single_mut().face = value;
-                return;
-            }
+    if !mouse_button_input.just_pressed(MouseButton::Left) {
+        return;
+    }
+    if let Some(pos) = cursor_from_middle(window.single()) {
+        clicks.send(Click(pos));
+    }
+}
 
-            if selected.single().face.is_none() {
-                return;
+/// The cursor in pixels from the middle of `window` with y pointing down, like a `Click`.
+pub fn cursor_from_middle(window: &Window) -> Option<Vec2> {
+    return window.cursor_position().map(|pos| pos - Vec2::new(window.width() / 2., window.height() / 2.));
+}
+
+pub fn mouse_click_system(
+    mut clicks: EventReader<Click>,
+    mut places: EventWriter<PlaceRequested>,
+    game: Query<&Game>,
+    board: Query<&Board>,
+    mut selected: Query<&mut Selected>,
+    inventory: Query<(&Inventory, &Color)>,
+    players: Res<Players>,
+    layout: Res<Layout>,
+    mut status: ResMut<StatusBar>,
+) {
+    for Click(pos) in clicks.read() {
+        if players.get(board.single().color) != Player::Human {
+            if square_at(&layout, *pos).is_some() {
+                status.message = Some(MoveError::WrongTurn.describe());
             }
+            continue;
+        }
+        // a networked game can only be played from its live position
+        let g = game.single();
+        if players.has_remote() && g.get_pos() + 1 != g.get_history().len() {
+            continue;
+        }
 
-            if pos.x - w >= -(BOARD_WIDTH + CARD_SIZE) / 2. && pos.x - w <= (BOARD_WIDTH + CARD_SIZE) / 2.
-                && pos.y - h >= -(BOARD_HEIGHT + CARD_SIZE) / 2. && pos.y - h <= (BOARD_HEIGHT + CARD_SIZE) / 2. 
-            {
-                let x = (pos.x - w + (BOARD_WIDTH + CARD_SIZE) / 2.) as u32 / ((BOARD_WIDTH + CARD_SIZE) as u32 / SIZE_X);
-                let y = (SIZE_Y - 1) - ((pos.y - h + (BOARD_HEIGHT + CARD_SIZE) / 2.) as u32 / ((BOARD_HEIGHT + CARD_SIZE) as u32 / SIZE_Y));
-                if let Some(face) = selected.single().face {
-                    let mut b = board.single_mut();
-                    let mut inv = inventory.iter_mut().find(|e| *e.1 == b.color).unwrap().0;
-                    if inv.can_place_face(face) 
-                    && b.can_place_tile(x, y, face).or(b.can_place_free(x, y, face)).is_ok() 
-                    {
-                        inv.place_face(face);
-                        let red_inv = inventory.iter().find(|e| *e.1 == Color::Red).unwrap().0.clone();
-                        let black_inv = inventory.iter().find(|e| *e.1 == Color::Black).unwrap().0.clone();
-                        selected.single_mut().face = None;
-                        b.place_tile(x, y, face).ok().unwrap();
-                        game.single_mut().add_move(x, y, face, b.clone(), red_inv.clone(), black_inv.clone());
-                        line_counts.0 = b.get_lines();
+        if pos.x >= -537.5 && pos.x <= -462.5 {
+            let mut value = selected.single().face;
+            for i in 0..4 {
+                let level = -240. + (i * 160) as f32; 
+                if pos.y >= level - 37.5 && pos.y <= level + 37.5 {
+                    let face = Face::from_num(3 - i);
+                    let inv = inventory.iter().find(|e| *e.1 == board.single().color).unwrap().0;
+                    if inv.can_place_face(face) {
+                        value = Some(Face::from_num(3 - i));
+                    } else {
+                        status.message = Some(MoveError::NoPieces.describe());
                     }
+                    break;
                 }
             }
+            selected.single_mut().face = value;
+            continue;
+        }
+
+        let Some(face) = selected.single().face else {
+            continue;
+        };
+        if let Some((x, y)) = square_at(&layout, *pos) {
+            places.send(PlaceRequested {mv: Move {x, y, face}, live: false});
         }
     }
-}
\ No newline at end of file
+}
+
+/// The square at `pos`, in pixels from the middle of the window with y pointing down.
+pub fn square_at(layout: &Layout, pos: Vec2) -> Option<(u32, u32)> {
+    let (size_x, size_y) = layout.size;
+    let width = layout.board_width() + layout.card_size;
+    let height = layout.board_height() + layout.card_size;
+    if pos.x < -width / 2. || pos.x > width / 2. || pos.y < -height / 2. || pos.y > height / 2. {
+        return None;
+    }
+    let x = ((pos.x + width / 2.) as u32 / (width as u32 / size_x)).min(size_x - 1);
+    let y = (size_y - 1) - ((pos.y + height / 2.) as u32 / (height as u32 / size_y)).min(size_y - 1);
+    return Some((x, y));
+}
+
+/// Where to click to pick `face` from the inventory.
+pub fn face_click(face: Face) -> Vec2 {
+    let i = match face {
+        Face::Ace => 0,
+        Face::King => 1,
+        Face::Queen => 2,
+        _ => 3
+    };
+    return Vec2::new(-500., -240. + (i * 160) as f32);
+}
+
+/// Where to click to place a face on the square at `x`, `y`.
+pub fn square_click(layout: &Layout, x: u32, y: u32) -> Vec2 {
+    let (size_x, size_y) = layout.size;
+    let width = layout.board_width() + layout.card_size;
+    let height = layout.board_height() + layout.card_size;
+    let cell_x = (width as u32 / size_x) as f32;
+    let cell_y = (height as u32 / size_y) as f32;
+    return Vec2::new(
+        (x as f32 + 0.5) * cell_x - width / 2.,
+        ((size_y - 1 - y) as f32 + 0.5) * cell_y - height / 2.
+    );
+}
@@ -0,0 +1,837 @@
+use std::{
+    io::{self, BufRead, BufReader, Write},
+    net::{Shutdown, TcpListener, TcpStream},
+    sync::{mpsc::{channel, Receiver, Sender}, Arc, Mutex},
+    thread,
+    time::{Duration, Instant}
+};
+
+use bevy::prelude::*;
+
+use crate::{
+    chat::Chat,
+    bot::{Player, Players},
+    events::{
+        GameEnded, GameReplaced, HistoryReplaced, MovePlayed, NavigateRequested, Navigation,
+        OfferAccepted, PlaceRejected, PlaceRequested, ResultDeclared
+    },
+    game::{EndReason, Game, GameResult, Move, Offer, Ruleset},
+    lan::{announce, Announcement},
+    offer::PendingOffer,
+    protocol::{new_token, random_id},
+    server::MAX_CHAT_LEN,
+    tile::Color
+};
+
+pub const DEFAULT_ADDRESS: &str = "127.0.0.1:7878";
+const RECONNECT_INTERVAL: Duration = Duration::from_secs(1);
+/// How many times a client tries to get back to the host before giving up.
+const RECONNECT_ATTEMPTS: u32 = 60;
+
+pub enum NetEvent {
+    Connected(u64, TcpStream),
+    Line(u64, String),
+    Disconnected(u64),
+    /// The client is about to try reconnecting for the `n`th time.
+    Reconnecting(u32),
+    GaveUp
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Role {
+    Host,
+    Client,
+    Spectator
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum NetState {
+    Waiting,
+    Connected,
+    Disconnected
+}
+
+struct Peer {
+    id: u64,
+    stream: TcpStream,
+    /// Whether it said `PLAY`, `WATCH` or `RESUME` and is sent what happens in the game.
+    joined: bool
+}
+
+/// A game played against another instance over TCP.  The host plays Red and
+/// keeps the authoritative game: it checks every move the client sends and
+/// answers an illegal one by sending its whole history back.  Anyone else who
+/// connects watches the game without being able to play.
+///
+/// The protocol is one message per line.  Whoever connects first sends
+/// `PLAY` to take the open seat or `WATCH` to spectate, and the host answers with
+/// `SYNC <ruleset> [clock <time control>] [position <position>] <move> <move> ...`,
+/// the full history of the game from its start, with the seconds spent on each
+/// move after an `@`, e.g. `Kd3@12.500`, if the game has a clock.  After that
+/// each `MOVE <move>` is a move in `Move` notation, sent by whoever played it and
+/// passed on by the host to the spectators.  Nothing is sent to a connection
+/// until it has said what it is there for.
+///
+/// The host answers `PLAY` with `WELCOME <game id> <token>` before the `SYNC`.
+/// If the client's connection drops, it keeps reconnecting for a while with
+/// `RESUME <game id> <token>` and gets its seat and the whole game back, unless
+/// it stays away longer than the forfeit timeout and loses the game.  The host
+/// answers a `RESUME` it does not recognize with `REFUSED`.
+///
+/// Either player may send `OFFER draw` or `OFFER takeback`, answered with `ACCEPT`
+/// or `DECLINE`, and the client may send `RESIGN`.  The host applies the outcome
+/// and sends everyone the game again, followed by `RESULT <result>` once it is over.
+///
+/// Anyone may send `CHAT <text>`.  The host passes it on to everyone, including
+/// whoever sent it, as `CHAT <speaker> <text>`, where the speaker is `red`, `black`
+/// or `spectator-<n>`.
+#[derive(Resource)]
+pub struct Network {
+    pub role: Role,
+    pub state: NetState,
+    address: String,
+    /// The host has everyone connected to it, the others only the host.
+    peers: Vec<Peer>,
+    opponent: Option<u64>,
+    events: Mutex<Receiver<NetEvent>>,
+    sender: Sender<NetEvent>,
+    next_peer: u64,
+    game_id: u64,
+    /// What the opponent needs to resume their seat, once there is one.
+    token: Option<String>,
+    /// When the opponent's connection dropped, while the host waits for them.
+    away: Option<Instant>,
+    /// The client's attempt at getting back to the host, while it is trying.
+    reconnecting: Option<u32>,
+    /// The client stopped trying to get back to the host.
+    gave_up: bool,
+    forfeit_timeout: Duration,
+    pub forfeited: Option<Color>,
+    /// What the host broadcasts about the game on the local network.
+    announcement: Option<Arc<Mutex<Announcement>>>,
+    /// The opponent's move the host asked to play, until it is known whether it was.
+    requested: Option<Move>
+}
+
+#[derive(Component)]
+pub struct NetUI;
+
+impl Network {
+    /// Listens on `address` and accepts an opponent and spectators in the background.
+    /// An opponent who drops out loses the game after `forfeit_timeout`.
+    pub fn host(address: &str, forfeit_timeout: Duration, ruleset: Ruleset) -> io::Result<Network> {
+        let listener = TcpListener::bind(address)?;
+        // the port is picked by the system when it is 0
+        let bound = listener.local_addr()?;
+        let (sender, receiver) = channel();
+        let listener_sender = sender.clone();
+        thread::spawn(move || {
+            let sender = listener_sender;
+            for (id, stream) in listener.incoming().enumerate() {
+                let Ok(stream) = stream else {
+                    continue;
+                };
+                let Ok(writer) = stream.try_clone() else {
+                    continue;
+                };
+                if sender.send(NetEvent::Connected(id as u64, writer)).is_err() {
+                    return;
+                }
+                let sender = sender.clone();
+                thread::spawn(move || read_lines(id as u64, stream, sender));
+            }
+        });
+
+        let mut network = Network {
+            role: Role::Host,
+            state: NetState::Waiting,
+            address: bound.to_string(),
+            peers: Vec::new(),
+            opponent: None,
+            events: Mutex::new(receiver),
+            sender,
+            next_peer: 0,
+            game_id: random_id(),
+            token: None,
+            away: None,
+            reconnecting: None,
+            gave_up: false,
+            forfeit_timeout,
+            forfeited: None,
+            announcement: None,
+            requested: None
+        };
+        // the game can still be joined by address if it cannot be announced
+        let announcement = Arc::new(Mutex::new(Announcement::new(network.game_id, bound.port(), ruleset.to_string(), Some(Color::Black))));
+        if announce(&announcement, bound.ip()).is_ok() {
+            network.announcement = Some(announcement);
+        }
+        return Ok(network);
+    }
+
+    pub fn connect(address: &str) -> io::Result<Network> {
+        return Network::join(address, Role::Client, "PLAY".to_string());
+    }
+
+    pub fn watch(address: &str) -> io::Result<Network> {
+        return Network::join(address, Role::Spectator, "WATCH".to_string());
+    }
+
+    /// Takes back a seat in a game that was left, e.g. because the game was closed.
+    pub fn resume(address: &str, game_id: u64, token: &str) -> io::Result<Network> {
+        let mut network = Network::join(address, Role::Client, format!("RESUME {game_id:016x} {token}"))?;
+        network.game_id = game_id;
+        network.token = Some(token.to_string());
+        return Ok(network);
+    }
+
+    fn join(address: &str, role: Role, hello: String) -> io::Result<Network> {
+        let (stream, writer) = open(address, &hello)?;
+        let (sender, receiver) = channel();
+        let reader_sender = sender.clone();
+        thread::spawn(move || read_lines(0, stream, reader_sender));
+
+        return Ok(Network {
+            role,
+            state: NetState::Connected,
+            address: address.to_string(),
+            peers: vec![Peer {id: 0, stream: writer, joined: true}],
+            opponent: Some(0),
+            events: Mutex::new(receiver),
+            sender,
+            next_peer: 1,
+            game_id: 0,
+            token: None,
+            away: None,
+            reconnecting: None,
+            gave_up: false,
+            forfeit_timeout: Duration::ZERO,
+            forfeited: None,
+            announcement: None,
+            requested: None
+        });
+    }
+
+    pub fn local_color(&self) -> Option<Color> {
+        match self.role {
+            Role::Host => Some(Color::Red),
+            Role::Client => Some(Color::Black),
+            Role::Spectator => None
+        }
+    }
+
+    pub fn remote_color(&self) -> Color {
+        match self.role {
+            Role::Client => Color::Red,
+            _ => Color::Black
+        }
+    }
+
+    pub fn players(&self) -> Players {
+        match self.role {
+            Role::Host => Players {red: Player::Human, black: Player::Remote},
+            Role::Client => Players {red: Player::Remote, black: Player::Human},
+            Role::Spectator => Players {red: Player::Remote, black: Player::Remote}
+        }
+    }
+
+    fn spectators(&self) -> usize {
+        return self.joined().into_iter().filter(|id| Some(*id) != self.opponent).count();
+    }
+
+    /// The peers taking part in the game, as a player or a spectator.
+    fn joined(&self) -> Vec<u64> {
+        return self.peers.iter().filter(|peer| peer.joined).map(|peer| peer.id).collect();
+    }
+
+    fn join_peer(&mut self, id: u64) {
+        if let Some(peer) = self.peers.iter_mut().find(|peer| peer.id == id) {
+            peer.joined = true;
+        }
+    }
+
+    /// Sends a line to one peer.  A peer that cannot be written to is shut down,
+    /// so its reader finishes and reports it as disconnected.
+    fn send(&mut self, id: u64, line: &str) {
+        let Some(peer) = self.peers.iter_mut().find(|peer| peer.id == id) else {
+            return;
+        };
+        if writeln!(peer.stream, "{line}").is_err() {
+            let _ = peer.stream.shutdown(Shutdown::Both);
+        }
+    }
+
+    fn send_sync(&mut self, id: u64, game: &Game) {
+        self.send(id, &sync_line(game));
+        if let Some(result) = game.get_result() {
+            self.send(id, &format!("RESULT {result}"));
+        }
+    }
+
+    /// Sends the whole game to everyone, after it changed other than by a move.
+    fn send_game(&mut self, game: &Game) {
+        for id in self.joined() {
+            self.send_sync(id, game);
+        }
+    }
+
+    fn send_opponent(&mut self, line: &str) {
+        if let Some(id) = self.opponent {
+            self.send(id, line);
+        }
+    }
+
+    /// Tells the host that the local player resigned.  The host sends everyone
+    /// the game itself once it is over.
+    pub fn resign(&mut self) {
+        if self.role != Role::Host {
+            self.send_opponent("RESIGN");
+        }
+    }
+
+    pub fn offer(&mut self, offer: Offer) {
+        let offer = match offer {
+            Offer::Draw => "draw",
+            Offer::Takeback => "takeback"
+        };
+        self.send_opponent(&format!("OFFER {offer}"));
+    }
+
+    /// Says `text` to everyone in the game.
+    pub fn chat(&mut self, text: &str) {
+        match self.role {
+            Role::Host => self.relay("red", text),
+            _ => self.send_opponent(&format!("CHAT {text}"))
+        }
+    }
+
+    fn relay(&mut self, speaker: &str, text: &str) {
+        for id in self.joined() {
+            self.send(id, &format!("CHAT {speaker} {text}"));
+        }
+    }
+
+    /// Answers the opponent's offer.
+    pub fn answer(&mut self, accepted: bool) {
+        self.send_opponent(if accepted {"ACCEPT"} else {"DECLINE"});
+    }
+}
+
+fn sync_line(game: &Game) -> String {
+    let mut line = format!("SYNC {}", game.get_ruleset());
+    if let Some(clock) = game.get_clock() {
+        line += &format!(" clock {}", clock.control);
+    }
+    let start = game.get_start().to_string();
+    if start != Game::new(game.get_ruleset()).get_start().to_string() {
+        line += &format!(" position {start}");
+    }
+    for ((mv, ..), time) in game.get_history().iter().zip(game.get_times()).skip(1) {
+        line += &format!(" {}", mv.unwrap());
+        if let Some(time) = time {
+            line += &format!("@{:.3}", time.as_secs_f64());
+        }
+    }
+    return line;
+}
+
+/// The game a `SYNC` line's arguments describe, replayed like a record.
+fn parse_sync(args: &str) -> Option<Game> {
+    let mut args = args.split_whitespace().peekable();
+    let mut record = args.next()?.to_string();
+    if args.next_if_eq(&"clock").is_some() {
+        record += &format!(" {}", args.next()?);
+    }
+    record += "\n";
+    if args.next_if_eq(&"position").is_some() {
+        let position: Vec<&str> = args.by_ref().take(4).collect();
+        record += &format!("position {}\n", position.join(" "));
+    }
+    for mv in args {
+        record += &format!("{}\n", mv.replacen('@', " ", 1));
+    }
+    return Game::from_record(&record).ok();
+}
+
+/// Connects to the host and says `hello`, returning the stream to read and one to write.
+fn open(address: &str, hello: &str) -> io::Result<(TcpStream, TcpStream)> {
+    let stream = TcpStream::connect(address)?;
+    stream.set_nodelay(true)?;
+    let mut writer = stream.try_clone()?;
+    writeln!(writer, "{hello}")?;
+    return Ok((stream, writer));
+}
+
+/// Tries to get back to the host every second until it answers, then reads its lines as peer `id`.
+/// Stops once the network game is gone or after `RECONNECT_ATTEMPTS` tries.
+fn reconnect(address: String, hello: String, id: u64, sender: Sender<NetEvent>) {
+    for attempt in 1..=RECONNECT_ATTEMPTS {
+        thread::sleep(RECONNECT_INTERVAL);
+        if sender.send(NetEvent::Reconnecting(attempt)).is_err() {
+            return;
+        }
+        if let Ok((stream, writer)) = open(&address, &hello) {
+            if sender.send(NetEvent::Connected(id, writer)).is_ok() {
+                read_lines(id, stream, sender);
+            }
+            return;
+        }
+    }
+    let _ = sender.send(NetEvent::GaveUp);
+}
+
+fn read_lines(id: u64, stream: TcpStream, sender: Sender<NetEvent>) {
+    for line in BufReader::new(stream).lines() {
+        let Ok(line) = line else {
+            break;
+        };
+        if sender.send(NetEvent::Line(id, line)).is_err() {
+            return;
+        }
+    }
+    let _ = sender.send(NetEvent::Disconnected(id));
+}
+
+pub fn network_system(
+    network: Option<ResMut<Network>>,
+    mut players: ResMut<Players>,
+    mut game: Query<&mut Game>,
+    mut played: EventReader<MovePlayed>,
+    mut rejected: EventReader<PlaceRejected>,
+    mut ended: EventReader<GameEnded>,
+    mut rewritten: EventReader<HistoryReplaced>,
+    mut places: EventWriter<PlaceRequested>,
+    mut navigate: EventWriter<NavigateRequested>,
+    mut accepted: EventWriter<OfferAccepted>,
+    mut results: EventWriter<ResultDeclared>,
+    mut replacements: EventWriter<GameReplaced>,
+    mut pending: ResMut<PendingOffer>,
+    mut chat: ResMut<Chat>,
+) {
+    let Some(mut network) = network else {
+        return;
+    };
+
+    // pass on the moves played here, and the opponent's moves to the spectators,
+    // before anything that might replace the game
+    for MovePlayed {mv, color} in played.read() {
+        if network.role == Role::Spectator {
+            continue;
+        }
+        let by_opponent = *color == network.remote_color();
+        let ids: Vec<u64> = network.joined().into_iter()
+            .filter(|id| !(by_opponent && network.opponent == Some(*id)))
+            .collect();
+        for id in ids {
+            network.send(id, &format!("MOVE {mv}"));
+        }
+    }
+    // a move of the opponent's that was turned down means it is out of step
+    let requested = network.requested.take();
+    if rejected.read().any(|rejected| rejected.live && Some(rejected.mv) == requested) {
+        if let Some(id) = network.opponent {
+            network.send_sync(id, game.single());
+        }
+    }
+    // the host's game is the one that counts, so everyone gets it again when it
+    // changed other than by a move, unless the board decided how it ended
+    let ended = ended.read().any(|GameEnded(result)| result.reason != EndReason::Lines);
+    if (rewritten.read().count() > 0 || ended) && network.role == Role::Host {
+        network.send_game(game.single());
+    }
+
+    let events: Vec<NetEvent> = network.events.lock().unwrap().try_iter().collect();
+    for event in events {
+        match event {
+            NetEvent::Connected(id, stream) => {
+                let _ = stream.set_nodelay(true);
+                // the host hears from its peers what they are there for
+                let joined = network.role != Role::Host;
+                network.peers.push(Peer {id, stream, joined});
+                if network.role == Role::Client {
+                    network.opponent = Some(id);
+                    network.state = NetState::Connected;
+                    network.reconnecting = None;
+                }
+            },
+            NetEvent::Line(id, line) => {
+                let (command, args) = line.trim().split_once(' ').unwrap_or((line.trim(), ""));
+                match (network.role, command) {
+                    (Role::Host, "PLAY" | "WATCH") => {
+                        let seat_open = players.get(network.remote_color()) == Player::Remote;
+                        if command == "PLAY" && network.opponent.is_none() && network.token.is_none() && seat_open {
+                            let token = new_token();
+                            let welcome = format!("WELCOME {:016x} {token}", network.game_id);
+                            network.send(id, &welcome);
+                            network.opponent = Some(id);
+                            network.state = NetState::Connected;
+                            network.token = Some(token);
+                        }
+                        network.join_peer(id);
+                        network.send_sync(id, game.single());
+                    },
+                    (Role::Host, "RESUME") => {
+                        let (game_id, token) = args.split_once(' ').unwrap_or((args, ""));
+                        let valid = u64::from_str_radix(game_id, 16).is_ok_and(|game_id| game_id == network.game_id)
+                            && network.token.as_deref() == Some(token)
+                            && network.forfeited.is_none();
+                        if !valid {
+                            network.send(id, "REFUSED");
+                            continue;
+                        }
+                        // the old connection may not have been noticed as dropped yet
+                        if let Some(old) = network.opponent.filter(|old| *old != id) {
+                            if let Some(peer) = network.peers.iter().find(|peer| peer.id == old) {
+                                let _ = peer.stream.shutdown(Shutdown::Both);
+                            }
+                        }
+                        network.opponent = Some(id);
+                        network.state = NetState::Connected;
+                        network.away = None;
+                        network.join_peer(id);
+                        network.send_sync(id, game.single());
+                    },
+                    (Role::Client, "WELCOME") => {
+                        let (game_id, token) = args.split_once(' ').unwrap_or((args, ""));
+                        if let Ok(game_id) = u64::from_str_radix(game_id, 16) {
+                            network.game_id = game_id;
+                            network.token = Some(token.to_string());
+                        }
+                    },
+                    (Role::Client, "REFUSED") => {
+                        network.token = None;
+                    },
+                    (Role::Host | Role::Client, "MOVE") if network.opponent == Some(id) => {
+                        let g = game.single();
+                        let live_color = g.get_history().last().unwrap().1.color;
+                        match args.parse::<Move>() {
+                            Ok(mv) if live_color == network.remote_color() => {
+                                navigate.send(NavigateRequested(Navigation::Last));
+                                places.send(PlaceRequested {mv, live: true});
+                                if network.role == Role::Host {
+                                    network.requested = Some(mv);
+                                }
+                            },
+                            _ if network.role == Role::Host => network.send_sync(id, g),
+                            _ => ()
+                        }
+                    },
+                    (Role::Spectator, "MOVE") => {
+                        // the screen only follows the game if it was being watched live
+                        if let Ok(mv) = args.parse() {
+                            places.send(PlaceRequested {mv, live: true});
+                        }
+                    },
+                    (Role::Host | Role::Client, "OFFER") if network.opponent == Some(id) => {
+                        let offer = match args {
+                            "draw" => Offer::Draw,
+                            "takeback" => Offer::Takeback,
+                            _ => continue
+                        };
+                        let g = game.single();
+                        if !g.is_over() {
+                            pending.make(network.remote_color(), offer, g);
+                        }
+                    },
+                    (Role::Host | Role::Client, "DECLINE") if network.opponent == Some(id) => {
+                        pending.answer(false);
+                    },
+                    (Role::Host, "ACCEPT") if network.opponent == Some(id) => {
+                        let Some((from, offer)) = pending.offer.filter(|(from, _)| *from != network.remote_color()) else {
+                            continue;
+                        };
+                        pending.answer(true);
+                        accepted.send(OfferAccepted {offer, from});
+                        navigate.send(NavigateRequested(Navigation::Last));
+                    },
+                    (Role::Client, "ACCEPT") if network.opponent == Some(id) => {
+                        pending.answer(true);
+                    },
+                    (Role::Host, "RESIGN") if network.opponent == Some(id) => {
+                        results.send(ResultDeclared(GameResult {winner: Some(Color::Red), reason: EndReason::Resignation}));
+                    },
+                    (Role::Client | Role::Spectator, "RESULT") => {
+                        if let Ok(result) = args.parse() {
+                            results.send(ResultDeclared(result));
+                        }
+                    },
+                    (Role::Host, "CHAT") => {
+                        let text: String = args.trim().chars().take(MAX_CHAT_LEN).collect();
+                        if text.is_empty() {
+                            continue;
+                        }
+                        let speaker = match network.opponent == Some(id) {
+                            true => "black".to_string(),
+                            false => format!("spectator-{id}")
+                        };
+                        game.single_mut().comment(format!("{speaker}: {text}"));
+                        chat.receive(&speaker, &text);
+                        network.relay(&speaker, &text);
+                    },
+                    (Role::Client | Role::Spectator, "CHAT") => {
+                        let (speaker, text) = args.split_once(' ').unwrap_or((args, ""));
+                        game.single_mut().comment(format!("{speaker}: {text}"));
+                        chat.receive(speaker, text);
+                    },
+                    (Role::Client | Role::Spectator, "SYNC") => {
+                        if let Some(mut synced) = parse_sync(args) {
+                            synced.copy_comments(game.single());
+                            replacements.send(GameReplaced(synced));
+                            navigate.send(NavigateRequested(Navigation::Last));
+                        }
+                    },
+                    _ => ()
+                }
+            },
+            NetEvent::Reconnecting(attempt) => network.reconnecting = Some(attempt),
+            NetEvent::GaveUp => {
+                network.reconnecting = None;
+                network.gave_up = true;
+            },
+            NetEvent::Disconnected(id) => {
+                network.peers.retain(|peer| peer.id != id);
+                if network.opponent == Some(id) {
+                    network.opponent = None;
+                    network.state = NetState::Disconnected;
+                    match network.role {
+                        Role::Host if network.forfeited.is_none() => network.away = Some(Instant::now()),
+                        Role::Client if network.token.is_some() => {
+                            let hello = format!("RESUME {:016x} {}", network.game_id, network.token.as_ref().unwrap());
+                            let (address, peer, sender) = (network.address.clone(), network.next_peer, network.sender.clone());
+                            network.next_peer += 1;
+                            thread::spawn(move || reconnect(address, hello, peer, sender));
+                        },
+                        Role::Spectator => (),
+                        // let whoever is left finish the game at this screen
+                        _ => match network.remote_color() {
+                            Color::Black => players.black = Player::Human,
+                            _ => players.red = Player::Human
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    // an opponent who stays away too long loses the game
+    if network.away.is_some_and(|away| away.elapsed() >= network.forfeit_timeout) {
+        network.away = None;
+        network.forfeited = Some(network.remote_color());
+        results.send(ResultDeclared(GameResult {winner: network.local_color(), reason: EndReason::Forfeit}));
+    }
+
+    if let Some(announcement) = &network.announcement {
+        let seat_open = network.token.is_none() && players.get(network.remote_color()) == Player::Remote;
+        announcement.lock().unwrap().open_seat = Some(network.remote_color()).filter(|_| seat_open);
+    }
+}
+
+pub fn update_net_panel(
+    network: Option<Res<Network>>,
+    mut panel: Query<&mut Text, With<NetUI>>,
+) {
+    let Some(network) = network else {
+        return;
+    };
+    let name = |color| match color {
+        Some(Color::Black) => "Black",
+        _ => "Red"
+    };
+    let color = name(network.local_color());
+    let mut value = match (network.role, network.state) {
+        _ if network.forfeited.is_some() => format!("{} forfeited, {color} wins", name(network.forfeited)),
+        (Role::Host, NetState::Waiting) => format!("Hosting on {}, waiting for an opponent", network.address),
+        (Role::Spectator, NetState::Connected) => format!("Watching {}", network.address),
+        (_, NetState::Connected) => format!("Connected to {}, you are {color}", network.address),
+        (Role::Host, _) => match network.away {
+            Some(away) => format!(
+                "Opponent disconnected, forfeits in {}s",
+                network.forfeit_timeout.saturating_sub(away.elapsed()).as_secs()
+            ),
+            None => "Opponent disconnected".to_string()
+        },
+        (Role::Client, _) if network.gave_up && network.token.is_some() => format!(
+            "Could not reconnect to {} (resume {:016x} {})",
+            network.address, network.game_id, network.token.as_ref().unwrap()
+        ),
+        (Role::Client, _) if network.token.is_some() => format!(
+            "Host disconnected, reconnecting, attempt {} of {RECONNECT_ATTEMPTS} (resume {:016x} {})",
+            network.reconnecting.unwrap_or(0), network.game_id, network.token.as_ref().unwrap()
+        ),
+        (_, _) => "Host disconnected".to_string()
+    };
+    if network.role == Role::Host && network.spectators() > 0 {
+        value += &format!(", {} watching", network.spectators());
+    }
+    panel.single_mut().sections[0].value = value;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{board::spawn_game, events::{add_events, apply_system}};
+
+    /// An app with just what a network game needs to exchange moves.
+    fn app(network: Network) -> App {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .init_resource::<PendingOffer>()
+            .init_resource::<Chat>()
+            .insert_resource(network.players())
+            .insert_resource(network);
+        add_events(&mut app);
+        app.add_systems(Startup, |mut commands: Commands| spawn_game(&mut commands, &Game::new(Ruleset::default())));
+        app.add_systems(Update, (network_system, apply_system).chain());
+        app.update();
+        return app;
+    }
+
+    fn game(app: &mut App) -> Game {
+        return app.world.query::<&Game>().single(&app.world).clone();
+    }
+
+    /// Updates the apps until `done` holds, failing after a few seconds.
+    fn run_until(apps: &mut [&mut App], mut done: impl FnMut(&mut [&mut App]) -> bool) {
+        let start = Instant::now();
+        while !done(apps) {
+            assert!(start.elapsed() < Duration::from_secs(5), "timed out");
+            for app in apps.iter_mut() {
+                app.update();
+            }
+            thread::sleep(Duration::from_millis(5));
+        }
+    }
+
+    fn host() -> (App, String) {
+        let network = Network::host("127.0.0.1:0", Duration::from_secs(60), Ruleset::default()).unwrap();
+        let address = network.address.clone();
+        return (app(network), address);
+    }
+
+    #[test]
+    fn moves_takebacks_and_results_reach_both_sides() {
+        let (mut host, address) = host();
+        let mut client = app(Network::connect(&address).unwrap());
+        run_until(&mut [&mut host, &mut client], |apps| {
+            apps[0].world.resource::<Network>().state == NetState::Connected
+                && apps[1].world.resource::<Network>().token.is_some()
+        });
+
+        let red: Move = "Kd3".parse().unwrap();
+        host.world.send_event(PlaceRequested {mv: red, live: true});
+        run_until(&mut [&mut host, &mut client], |apps| game(apps[1]).get_history().len() == 2);
+        assert_eq!(game(&mut client).get_history()[1].0, Some(red));
+
+        let black: Move = "Qe4".parse().unwrap();
+        client.world.send_event(PlaceRequested {mv: black, live: true});
+        run_until(&mut [&mut host, &mut client], |apps| game(apps[0]).get_history().len() == 3);
+        assert_eq!(game(&mut host).get_history()[2].0, Some(black));
+
+        // the host applies a takeback and sends the client the game
+        host.world.send_event(OfferAccepted {offer: Offer::Takeback, from: Color::Black});
+        run_until(&mut [&mut host, &mut client], |apps| game(apps[1]).get_history().len() == 2);
+        assert_eq!(game(&mut host).get_history().len(), 2);
+
+        client.world.resource_mut::<Network>().resign();
+        run_until(&mut [&mut host, &mut client], |apps| game(apps[1]).is_over());
+        let result = GameResult {winner: Some(Color::Red), reason: EndReason::Resignation};
+        assert_eq!(game(&mut host).get_result(), Some(result));
+        assert_eq!(game(&mut client).get_result(), Some(result));
+    }
+
+    /// Connects without a `Network`, returning the stream to write and the lines read.
+    fn raw(address: &str) -> (TcpStream, Receiver<String>) {
+        let stream = TcpStream::connect(address).unwrap();
+        let writer = stream.try_clone().unwrap();
+        let (sender, receiver) = channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stream).lines().map_while(Result::ok) {
+                let _ = sender.send(line);
+            }
+        });
+        return (writer, receiver);
+    }
+
+    fn next_line(host: &mut App, lines: &Receiver<String>) -> String {
+        let mut line = None;
+        run_until(&mut [host], |_| {
+            line = lines.try_recv().ok();
+            return line.is_some();
+        });
+        return line.unwrap();
+    }
+
+    #[test]
+    fn bad_move_resyncs_and_drop_marks_away() {
+        let (mut host, address) = host();
+        let (mut writer, lines) = raw(&address);
+        let next_line = |host: &mut App| next_line(host, &lines);
+
+        writeln!(writer, "PLAY").unwrap();
+        assert!(next_line(&mut host).starts_with("WELCOME "));
+        assert_eq!(next_line(&mut host), format!("SYNC {}", Ruleset::default()));
+
+        // neither a move out of turn nor one that is not a move is taken
+        for line in ["MOVE Kd3", "MOVE zz"] {
+            writeln!(writer, "{line}").unwrap();
+            assert_eq!(next_line(&mut host), format!("SYNC {}", Ruleset::default()));
+            assert_eq!(game(&mut host).get_history().len(), 1);
+        }
+        // only the opponent's moves being turned down sends the game again
+        host.world.send_event(PlaceRequested {mv: "Kd4".parse().unwrap(), live: true});
+        host.update();
+        host.update();
+        thread::sleep(Duration::from_millis(50));
+        assert!(lines.try_recv().is_err());
+
+        writer.shutdown(Shutdown::Both).unwrap();
+        run_until(&mut [&mut host], |apps| apps[0].world.resource::<Network>().away.is_some());
+        assert_eq!(host.world.resource::<Network>().state, NetState::Disconnected);
+    }
+
+    #[test]
+    fn moves_go_only_to_peers_in_the_game() {
+        let (mut host, address) = host();
+        let (mut watcher, watched) = raw(&address);
+        let (_silent, heard) = raw(&address);
+        writeln!(watcher, "WATCH").unwrap();
+        assert_eq!(next_line(&mut host, &watched), format!("SYNC {}", Ruleset::default()));
+
+        host.world.send_event(PlaceRequested {mv: "Kd3".parse().unwrap(), live: true});
+        assert_eq!(next_line(&mut host, &watched), "MOVE Kd3");
+        thread::sleep(Duration::from_millis(50));
+        assert!(heard.try_recv().is_err());
+        assert_eq!(host.world.resource::<Network>().spectators(), 1);
+    }
+
+    #[test]
+    fn sync_keeps_the_clock() {
+        let mut game = Game::with_clock(Ruleset::default(), "300+3".parse().unwrap());
+        for (mv, secs) in [("Kd3", 12.5), ("Qe4", 40.)] {
+            game.tick(Duration::from_secs_f64(secs));
+            game.play(mv.parse().unwrap()).unwrap();
+        }
+        let line = sync_line(&game);
+        assert_eq!(line, "SYNC 7x7 clock 300+3 Kd3@12.500 Qe4@40.000");
+
+        let synced = parse_sync(line.strip_prefix("SYNC ").unwrap()).unwrap();
+        assert_eq!(synced.get_times(), game.get_times());
+        let (clock, synced_clock) = (game.get_clock().unwrap(), synced.get_clock().unwrap());
+        assert_eq!(synced_clock.control, clock.control);
+        for color in [Color::Red, Color::Black] {
+            assert_eq!(synced_clock.get_remaining(color, Color::Red), clock.get_remaining(color, Color::Red));
+        }
+    }
+
+    #[test]
+    fn sync_keeps_the_start() {
+        let mut game = Game::new(Ruleset::default());
+        game.set_start("......./......./......./...W.../...K.../......./....... b 3233 3333".parse().unwrap());
+        game.play("Qe4".parse().unwrap()).unwrap();
+        let line = sync_line(&game);
+        assert_eq!(line, "SYNC 7x7 position ......./......./......./...W.../...K.../......./....... b 3233 3333 Qe4");
+
+        let synced = parse_sync(line.strip_prefix("SYNC ").unwrap()).unwrap();
+        assert_eq!(synced.to_record(), game.to_record());
+    }
+}
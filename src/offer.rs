@@ -0,0 +1,214 @@
+use bevy::prelude::*;
+
+use crate::{
+    bot::{BotTask, Player, Players},
+    eval::Evaluator,
+    events::{NavigateRequested, Navigation, OfferAccepted, ResultDeclared},
+    game::{EndReason, Game, GameResult, Offer},
+    net::{Network, Role},
+    tile::Color
+};
+
+/// An offer waiting for the other player's answer.
+#[derive(Resource, Default)]
+pub struct PendingOffer {
+    /// The offer and who made it.
+    pub offer: Option<(Color, Offer)>,
+    /// Length of the history when it was made, a move turns it down.
+    plies: usize,
+    /// What became of the last offer.
+    pub answer: Option<String>
+}
+
+impl PendingOffer {
+    pub fn make(&mut self, from: Color, offer: Offer, game: &Game) {
+        self.offer = Some((from, offer));
+        self.plies = game.get_history().len();
+        self.answer = None;
+    }
+
+    pub fn answer(&mut self, accepted: bool) {
+        if let Some((from, offer)) = self.offer.take() {
+            let offer = match offer {
+                Offer::Draw => "draw",
+                Offer::Takeback => "takeback"
+            };
+            let by = color_name(from.opponent());
+            self.answer = Some(format!("{by} {} the {offer}", if accepted {"accepted"} else {"declined"}));
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct OfferUI;
+
+fn color_name(color: Color) -> &'static str {
+    match color {
+        Color::Black => "Black",
+        _ => "Red"
+    }
+}
+
+/// R resigns, D offers a draw and T asks for a takeback, for the player at this
+/// screen or, when two people play here, the player to move.  Y accepts the opponent's
+/// offer and X declines it.  A bot takes every takeback and any draw it is not winning.
+pub fn offer_system(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    players: Res<Players>,
+    evaluator: Res<Evaluator>,
+    network: Option<ResMut<Network>>,
+    mut pending: ResMut<PendingOffer>,
+    mut bot_task: ResMut<BotTask>,
+    game: Query<&Game>,
+    mut navigate: EventWriter<NavigateRequested>,
+    mut accepted_offers: EventWriter<OfferAccepted>,
+    mut results: EventWriter<ResultDeclared>,
+) {
+    let mut network = network;
+    let g = game.single();
+    if pending.offer.is_some() && (pending.plies != g.get_history().len() || g.is_over()) {
+        pending.offer = None;
+    }
+    if g.is_over() {
+        return;
+    }
+
+    let me = match (&network, players.red, players.black) {
+        (Some(network), ..) => network.local_color(),
+        (None, Player::Human, Player::Human) => Some(g.get_history().last().unwrap().1.color),
+        (None, Player::Human, _) => Some(Color::Red),
+        (None, _, Player::Human) => Some(Color::Black),
+        (None, ..) => None
+    };
+    let Some(me) = me else {
+        return;
+    };
+
+    if keyboard_input.just_pressed(KeyCode::KeyR) {
+        bot_task.cancel();
+        results.send(ResultDeclared(GameResult {winner: Some(me.opponent()), reason: EndReason::Resignation}));
+        if let Some(network) = &mut network {
+            network.resign();
+        }
+        return;
+    }
+
+    let offer = if keyboard_input.just_pressed(KeyCode::KeyD) {
+        Some(Offer::Draw)
+    } else if keyboard_input.just_pressed(KeyCode::KeyT) {
+        Some(Offer::Takeback)
+    } else {
+        None
+    };
+    if let Some(offer) = offer {
+        pending.make(me, offer, g);
+        if let Some(network) = &mut network {
+            network.offer(offer);
+        } else if players.get(me.opponent()) == Player::Bot {
+            let (_, live, (red_inv, black_inv)) = g.get_history().last().unwrap();
+            let score = evaluator.evaluate(live, red_inv, black_inv);
+            let score = if me.opponent() == Color::Red {score} else {-score};
+            let accepted = offer == Offer::Takeback || score <= 0.;
+            if accepted {
+                bot_task.cancel();
+                accepted_offers.send(OfferAccepted {offer, from: me});
+                navigate.send(NavigateRequested(Navigation::Last));
+            }
+            pending.answer(accepted);
+        }
+        return;
+    }
+
+    let accepted = if keyboard_input.just_pressed(KeyCode::KeyY) {
+        true
+    } else if keyboard_input.just_pressed(KeyCode::KeyX) {
+        false
+    } else {
+        return;
+    };
+    // over the network only the opponent's offers are answered here
+    let Some((from, offer)) = pending.offer.filter(|(from, _)| network.is_none() || *from != me) else {
+        return;
+    };
+    pending.answer(accepted);
+    if let Some(network) = &mut network {
+        network.answer(accepted);
+        // the host keeps the game, the client gets it back once the host has applied the offer
+        if network.role != Role::Host {
+            return;
+        }
+    }
+    if accepted {
+        bot_task.cancel();
+        accepted_offers.send(OfferAccepted {offer, from});
+        navigate.send(NavigateRequested(Navigation::Last));
+    }
+}
+
+pub fn update_offer_panel(
+    pending: Res<PendingOffer>,
+    game: Query<&Game>,
+    mut panel: Query<&mut Text, With<OfferUI>>,
+) {
+    let value = match (game.single().get_result(), pending.offer) {
+        (Some(result), _) => result.describe(),
+        (None, Some((from, Offer::Draw))) => format!("{} offers a draw, Y to accept, X to decline", color_name(from)),
+        (None, Some((from, Offer::Takeback))) => format!("{} asks for a takeback, Y to accept, X to decline", color_name(from)),
+        (None, None) => pending.answer.clone().unwrap_or_default()
+    };
+    panel.single_mut().sections[0].value = value;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        board::spawn_game,
+        events::{add_events, apply_system, PlaceRequested},
+        game::Ruleset
+    };
+
+    fn app(players: Players) -> App {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .init_resource::<ButtonInput<KeyCode>>()
+            .init_resource::<PendingOffer>()
+            .init_resource::<BotTask>()
+            .init_resource::<Evaluator>()
+            .insert_resource(players);
+        add_events(&mut app);
+        app.add_systems(Startup, |mut commands: Commands| spawn_game(&mut commands, &Game::new(Ruleset::default())));
+        app.add_systems(Update, (offer_system, apply_system).chain());
+        app.update();
+        return app;
+    }
+
+    fn press(app: &mut App, key: KeyCode) {
+        app.world.resource_mut::<ButtonInput<KeyCode>>().press(key);
+        app.update();
+        app.world.resource_mut::<ButtonInput<KeyCode>>().reset_all();
+        app.update();
+    }
+
+    fn game(app: &mut App) -> Game {
+        return app.world.query::<&Game>().single(&app.world).clone();
+    }
+
+    #[test]
+    fn keys_act_for_the_human_on_the_bots_turn() {
+        let mut app = app(Players {red: Player::Human, black: Player::Bot});
+        app.world.send_event(PlaceRequested {mv: "Kd3".parse().unwrap(), live: true});
+        app.update();
+        assert_eq!(game(&mut app).get_history().len(), 2);
+
+        // the bot takes back Red's move rather than being asked for one
+        press(&mut app, KeyCode::KeyT);
+        assert_eq!(game(&mut app).get_history().len(), 1);
+        assert_eq!(app.world.resource::<PendingOffer>().answer.as_deref(), Some("Black accepted the takeback"));
+
+        app.world.send_event(PlaceRequested {mv: "Kd3".parse().unwrap(), live: true});
+        app.update();
+        press(&mut app, KeyCode::KeyR);
+        assert_eq!(game(&mut app).get_result().and_then(|result| result.winner), Some(Color::Black));
+    }
+}
@@ -0,0 +1,144 @@
+use std::path::PathBuf;
+
+use crate::{
+    bot::{Player, Players},
+    clock::TimeControl,
+    game::{parse_size, Game, Position, RecordError, Ruleset}
+};
+
+pub const USAGE: &str = "\
+usage: descend [options] [host [address] [forfeit timeout in seconds] | connect [address] | watch [address] | join
+               | resume <address> <game id> <token> | correspond <red|black> <dir> [game id] | tui
+               | headless [script]]
+
+options:
+  --size <x>x<y>           board size, odd sides up to 25, 7x7 by default
+  --rules <variant>        standard (three of each face), quick (two) or long (four)
+  --load <record>          carry on from a game record
+  --position <position>    start from a position, e.g. \"......./......./......./...W.../......./......./....... r 3333 3333\"
+  --red <player>           human, bot or engine:<command>, human by default
+  --black <player>         the same for Black
+  --ui <window|terminal>   the front-end, a window by default";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FrontEnd {
+    Window,
+    Terminal
+}
+
+/// Where a game starts, other than an empty board.
+#[derive(Clone)]
+pub enum Start {
+    Record(PathBuf),
+    Position(Position)
+}
+
+#[derive(Debug)]
+pub enum OptionsError {
+    Unknown(String),
+    /// An option without its value.
+    Missing(String),
+    Invalid(String, String),
+    /// Options that cannot be used together.
+    Conflict(String, String)
+}
+
+/// What the game was launched with.
+#[derive(Clone)]
+pub struct Options {
+    pub ruleset: Ruleset,
+    pub start: Option<Start>,
+    pub players: Players,
+    /// The commands that run the engines playing Red and Black.
+    pub engines: [Option<String>; 2],
+    pub front_end: FrontEnd,
+    /// The mode and its arguments, e.g. `host 0.0.0.0:7878`.
+    pub mode: Vec<String>
+}
+
+impl Default for Options {
+    fn default() -> Options {
+        Options {
+            ruleset: Ruleset::default(),
+            start: None,
+            players: Players {red: Player::Human, black: Player::Human},
+            engines: [None, None],
+            front_end: FrontEnd::Window,
+            mode: Vec::new()
+        }
+    }
+}
+
+impl Options {
+    /// Reads the options from the arguments after the program's name.
+    /// Anything that is not an option is taken as the mode and its arguments.
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Options, OptionsError> {
+        let mut options = Options::default();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            if !arg.starts_with("--") {
+                options.mode.push(arg);
+                continue;
+            }
+            let value = args.next().ok_or(OptionsError::Missing(arg.clone()))?;
+            let invalid = || OptionsError::Invalid(arg.clone(), value.clone());
+            match arg.as_str() {
+                "--size" => options.ruleset.size = parse_size(&value).map_err(|_| invalid())?,
+                "--rules" => options.ruleset.variant = value.parse().map_err(|_| invalid())?,
+                "--load" => options.start = Some(Start::Record(PathBuf::from(&value))),
+                "--position" => options.start = Some(Start::Position(value.parse().map_err(|_| invalid())?)),
+                "--red" | "--black" => {
+                    let (player, engine) = match value.split_once(':') {
+                        Some(("engine", command)) if !command.trim().is_empty() => (Player::Engine, Some(command.to_string())),
+                        None if value == "human" => (Player::Human, None),
+                        None if value == "bot" => (Player::Bot, None),
+                        _ => return Err(invalid())
+                    };
+                    let i = (arg == "--black") as usize;
+                    options.engines[i] = engine;
+                    match i {
+                        0 => options.players.red = player,
+                        _ => options.players.black = player
+                    }
+                },
+                "--ui" => options.front_end = match value.as_str() {
+                    "window" => FrontEnd::Window,
+                    "terminal" => FrontEnd::Terminal,
+                    _ => return Err(invalid())
+                },
+                _ => return Err(OptionsError::Unknown(arg))
+            }
+        }
+
+        let mode = options.mode.first().cloned().unwrap_or_default();
+        if mode == "tui" {
+            options.front_end = FrontEnd::Terminal;
+        }
+        // only local games can be played in the terminal, or start from a position
+        if options.front_end == FrontEnd::Terminal && !matches!(mode.as_str(), "" | "tui") {
+            return Err(OptionsError::Conflict("--ui terminal".to_string(), mode));
+        }
+        if matches!(options.start, Some(Start::Position(_))) && !matches!(mode.as_str(), "" | "tui" | "headless") {
+            return Err(OptionsError::Conflict("--position".to_string(), mode));
+        }
+        // a correspondence game carries on from the move files in its folder
+        if matches!(options.start, Some(Start::Record(_))) && mode == "correspond" {
+            return Err(OptionsError::Conflict("--load".to_string(), mode));
+        }
+        return Ok(options);
+    }
+
+    /// The game to play, with `clock` unless it is loaded from a record.
+    pub fn game(&self, clock: Option<TimeControl>) -> Result<Game, RecordError> {
+        let mut game = match (&self.start, clock) {
+            (Some(Start::Record(path)), _) => Game::load(path)?,
+            (_, Some(control)) => Game::with_clock(self.ruleset, control),
+            (_, None) => Game::new(self.ruleset)
+        };
+        if let Some(Start::Position(position)) = &self.start {
+            game.set_start(position.clone());
+        }
+        game.last_move();
+        return Ok(game);
+    }
+}
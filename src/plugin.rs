@@ -0,0 +1,381 @@
+use bevy::{prelude::*, render::color::Color as BevyColor, sprite::{MaterialMesh2dBundle, Mesh2dHandle}};
+
+use crate::{
+    board::{resize_board_system, spawn_board, spawn_game, update_screen},
+    book::{update_book_panel, Book, BookUI},
+    bot::{bot_system, update_bot_panel, BotTask, BotUI, Player, Players},
+    chat::{chat_system, update_chat_panel, Chat, ChatInput, ChatPanel, ChatText, ChatUI},
+    clock::{clock_system, update_clock_panel, ClockUI, TimeControl},
+    correspondence::{correspondence_system, update_correspondence_panel},
+    engine::{engine_system, Engines},
+    eval::{update_eval_panel, EvalUI, Evaluator},
+    events::{add_events, apply_system},
+    game::{Game, Ruleset},
+    keyboard::keyboard_system,
+    lan::{discovery_system, update_discovery_panel},
+    lines::{line_hover_system, line_overlay_system, LineOverlay},
+    menu::{
+        despawn_screen, game_over_system, game_running, leave_game_system, menu_button_system, menu_key_system,
+        new_game_system, spawn_game_over_screen, spawn_main_menu, spawn_pause_screen, spawn_setup_screen,
+        update_menu_labels, AppState, GameSetup
+    },
+    mouse::{cursor_system, mouse_click_system, Click},
+    net::{network_system, update_net_panel, NetUI},
+    offer::{offer_system, update_offer_panel, OfferUI, PendingOffer},
+    preview::{preview_system, update_preview_counts, Preview},
+    search::SearchConfig,
+    status::{status_system, update_status_bar, StatusBar, StatusUI},
+    tile::{Color, Face},
+    InventoryUI,
+    Theme
+};
+
+/// Descend in a window, for an app that already has `DefaultPlugins`, e.g.
+/// `app.add_plugins(DescendPlugin {players, ..default()})`.  It opens on the main menu, where
+/// new games are set up starting from `ruleset` and `players`, unless there is a game to `start`
+/// with.  To play someone elsewhere, insert a `Network`, `Discovery` or `Correspondence` too.
+/// Calling `Computer::insert`, e.g. `computer.insert(&mut app)`, before adding the plugin gives
+/// the bots its book, weights and engines, otherwise they play without them.
+pub struct DescendPlugin {
+    pub ruleset: Ruleset,
+    pub players: Players,
+    pub theme: Theme,
+    /// The clock new games are played with.
+    pub clock: Option<TimeControl>,
+    /// A game to play straight away, skipping the menu.
+    pub start: Option<Game>
+}
+
+impl Default for DescendPlugin {
+    fn default() -> DescendPlugin {
+        DescendPlugin {
+            ruleset: Ruleset::default(),
+            players: Players {red: Player::Human, black: Player::Human},
+            theme: Theme::default(),
+            clock: None,
+            start: None
+        }
+    }
+}
+
+/// The game the window opens with.
+#[derive(Resource)]
+struct Start(Game);
+
+impl Plugin for DescendPlugin {
+    fn build(&self, app: &mut App) {
+        let new_game = GameSetup {ruleset: self.ruleset, players: self.players, clock: self.clock};
+        let (game, state) = match &self.start {
+            Some(game) => (game.clone(), AppState::InGame),
+            None => (new_game.game(), AppState::MainMenu)
+        };
+        app.insert_state(state)
+            .insert_resource(new_game)
+            .init_resource::<Book>()
+            .init_resource::<Evaluator>()
+            .init_resource::<SearchConfig>()
+            .init_resource::<Engines>()
+            .init_resource::<BotTask>()
+            .init_resource::<LineOverlay>()
+            .init_resource::<Preview>()
+            .init_resource::<StatusBar>()
+            .insert_resource(self.players)
+            .insert_resource(self.theme)
+            .insert_resource(Start(game))
+            .add_event::<Click>();
+        add_events(app);
+        let playing = in_state(AppState::InGame);
+        app.add_systems(Startup, setup)
+            .add_systems(OnEnter(AppState::MainMenu), (leave_game_system, spawn_main_menu))
+            .add_systems(OnEnter(AppState::NewGameSetup), spawn_setup_screen)
+            .add_systems(OnEnter(AppState::Paused), spawn_pause_screen)
+            .add_systems(OnEnter(AppState::GameOver), spawn_game_over_screen)
+            .add_systems(OnTransition {from: AppState::NewGameSetup, to: AppState::InGame}, new_game_system)
+            .add_systems(OnTransition {from: AppState::GameOver, to: AppState::InGame}, new_game_system)
+            .add_systems(Update, (
+                (
+                    chat_system.run_if(playing.clone()), clock_system.run_if(game_running), discovery_system, network_system,
+                    correspondence_system, cursor_system.run_if(playing.clone()), mouse_click_system.run_if(playing.clone()),
+                    keyboard_system.run_if(playing.clone().or_else(in_state(AppState::Analysis))), offer_system.run_if(playing.clone())
+                ).chain(),
+                bot_system.run_if(game_running), engine_system.run_if(game_running), apply_system,
+                (menu_key_system, menu_button_system, game_over_system).chain(),
+                resize_board_system, update_screen, (line_hover_system, line_overlay_system, preview_system, update_preview_counts).chain(),
+                (status_system, update_status_bar).chain(), update_book_panel, update_eval_panel, update_bot_panel, update_net_panel,
+                update_discovery_panel, update_correspondence_panel, update_clock_panel, update_offer_panel,
+                update_chat_panel, update_menu_labels
+            ).chain());
+        for state in [AppState::MainMenu, AppState::NewGameSetup, AppState::Paused, AppState::GameOver] {
+            app.add_systems(OnExit(state), despawn_screen);
+        }
+    }
+}
+
+fn setup(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    start: Res<Start>,
+    theme: Res<Theme>
+) {
+    let Start(game) = start.into_inner();
+    let red_text_style = TextStyle {
+        font: default(),
+        font_size: 60.,
+        color: theme.red
+    };
+    let red_line_text_style = TextStyle {
+        font: default(),
+        font_size: 30.,
+        color: theme.red
+    };
+    let black_text_style = TextStyle {
+        font: default(),
+        font_size: 60.,
+        color: theme.black
+    };
+    let black_line_text_style = TextStyle {
+        font: default(),
+        font_size: 30.,
+        color: theme.black
+    };
+    let card_text_style = TextStyle {
+        font: default(),
+        font_size: 60.,
+        color: theme.card_text
+    };
+    commands.spawn(Camera2dBundle::default());
+
+    spawn_board(&mut commands, &mut meshes, &mut materials, &theme, &game.get_move().1);
+    
+    for i in 0..4 {
+        commands.spawn(MaterialMesh2dBundle {
+            mesh: Mesh2dHandle(meshes.add(Rectangle::new(75., 75.))),
+            material: materials.add(theme.red),
+            transform: Transform::from_xyz(-500., 240. - (i * 160) as f32, 0.),
+            ..default()
+        });
+        commands.spawn((Text2dBundle {
+            text: Text::from_section(match i {
+                0 => "A",
+                1 => "K",
+                2 => "Q",
+                3 => "J",
+                _ => unreachable!()
+            }, card_text_style.clone()),
+            transform: Transform::from_xyz(-500., 240. - (i * 160) as f32, 1.),
+            ..default()
+        }, InventoryUI));
+    }
+    commands.spawn(
+        (Text2dBundle {
+            text: Text::from_section("A * * *", red_text_style.clone()),
+            text_anchor: bevy::sprite::Anchor::Center,
+            transform: Transform::from_xyz(500., 300., 0.),
+            ..default()
+        },
+        Color::Red, 
+        Face::Ace
+    ));
+    commands.spawn(
+        (Text2dBundle {
+            text: Text::from_section("K * * *", red_text_style.clone()),
+            text_anchor: bevy::sprite::Anchor::Center,
+            transform: Transform::from_xyz(500., 252., 0.),
+            ..default()
+        },
+        Color::Red, 
+        Face::King
+    ));
+    commands.spawn(
+        (Text2dBundle {
+            text: Text::from_section("Q * * *", red_text_style.clone()),
+            text_anchor: bevy::sprite::Anchor::Center,
+            transform: Transform::from_xyz(500., 204., 0.),
+            ..default()
+        },
+        Color::Red, 
+        Face::Queen
+    ));
+    commands.spawn(
+        (Text2dBundle {
+            text: Text::from_section("J * * *", red_text_style.clone()),
+            text_anchor: bevy::sprite::Anchor::Center,
+            transform: Transform::from_xyz(500., 156., 0.),
+            ..default()
+        },
+        Color::Red, 
+        Face::Jack
+    ));
+    commands.spawn(
+        (Text2dBundle {
+            text: Text::from_section("A * * *", black_text_style.clone()),
+            text_anchor: bevy::sprite::Anchor::Center,
+            transform: Transform::from_xyz(500., -156., 0.),
+            ..default()
+        },
+        Color::Black, 
+        Face::Ace
+    ));
+    commands.spawn(
+        (Text2dBundle {
+            text: Text::from_section("K * * *", black_text_style.clone()),
+            text_anchor: bevy::sprite::Anchor::Center,
+            transform: Transform::from_xyz(500., -204., 0.),
+            ..default()
+        },
+        Color::Black, 
+        Face::King
+    ));
+    commands.spawn(
+        (Text2dBundle {
+            text: Text::from_section("Q * * *", black_text_style.clone()),
+            text_anchor: bevy::sprite::Anchor::Center,
+            transform: Transform::from_xyz(500., -252., 0.),
+            ..default()
+        },
+        Color::Black, 
+        Face::Queen
+    ));
+    commands.spawn(
+        (Text2dBundle {
+            text: Text::from_section("J * * *", black_text_style.clone()),
+            text_anchor: bevy::sprite::Anchor::Center,
+            transform: Transform::from_xyz(500., -300., 0.),
+            ..default()
+        },
+        Color::Black, 
+        Face::Jack
+    ));
+    commands.spawn((Text2dBundle {
+        text: Text::from_section("", red_line_text_style.clone()),
+        text_anchor: bevy::sprite::Anchor::Center,
+        transform: Transform::from_xyz(500., 100., 0.),
+        ..default()
+    }, Color::Red, ClockUI));
+    commands.spawn((Text2dBundle {
+        text: Text::from_section("", black_line_text_style.clone()),
+        text_anchor: bevy::sprite::Anchor::Center,
+        transform: Transform::from_xyz(500., -100., 0.),
+        ..default()
+    }, Color::Black, ClockUI));
+    commands.spawn((TextBundle::from_section(
+        "Book",
+        TextStyle {
+            font: default(),
+            font_size: 20.,
+            color: theme.text
+        }
+    ).with_style(Style {
+        position_type: PositionType::Absolute,
+        bottom: Val::Px(10.),
+        left: Val::Px(10.),
+        ..Default::default()
+    }), BookUI));
+
+    commands.spawn((TextBundle::from_section(
+        "Eval",
+        TextStyle {
+            font: default(),
+            font_size: 20.,
+            color: theme.text
+        }
+    ).with_style(Style {
+        position_type: PositionType::Absolute,
+        bottom: Val::Px(10.),
+        right: Val::Px(10.),
+        ..Default::default()
+    }), EvalUI));
+
+    commands.spawn((TextBundle::from_section(
+        "",
+        TextStyle {
+            font: default(),
+            font_size: 20.,
+            color: theme.text
+        }
+    ).with_style(Style {
+        position_type: PositionType::Absolute,
+        bottom: Val::Px(10.),
+        left: Val::Percent(35.),
+        ..Default::default()
+    }), BotUI));
+
+    commands.spawn((TextBundle::from_section(
+        "",
+        TextStyle {
+            font: default(),
+            font_size: 20.,
+            color: theme.text
+        }
+    ).with_style(Style {
+        position_type: PositionType::Absolute,
+        bottom: Val::Px(40.),
+        left: Val::Percent(35.),
+        ..Default::default()
+    }), StatusUI));
+
+    commands.spawn((TextBundle::from_section(
+        "",
+        TextStyle {
+            font: default(),
+            font_size: 20.,
+            color: theme.text
+        }
+    ).with_style(Style {
+        position_type: PositionType::Absolute,
+        top: Val::Px(10.),
+        left: Val::Px(10.),
+        ..Default::default()
+    }), NetUI));
+
+    commands.init_resource::<PendingOffer>();
+    commands.spawn((TextBundle::from_section(
+        "",
+        TextStyle {
+            font: default(),
+            font_size: 20.,
+            color: theme.text
+        }
+    ).with_style(Style {
+        position_type: PositionType::Absolute,
+        top: Val::Px(10.),
+        right: Val::Px(10.),
+        ..Default::default()
+    }), OfferUI));
+
+    commands.init_resource::<Chat>();
+    let chat_text_style = TextStyle {
+        font: default(),
+        font_size: 18.,
+        color: theme.text
+    };
+    commands.spawn((NodeBundle {
+        style: Style {
+            position_type: PositionType::Absolute,
+            top: Val::Percent(30.),
+            left: Val::Px(10.),
+            width: Val::Px(320.),
+            flex_direction: FlexDirection::Column,
+            ..Default::default()
+        },
+        visibility: Visibility::Hidden,
+        ..default()
+    }, ChatUI)).with_children(|parent| {
+        parent.spawn((NodeBundle {
+            style: Style {
+                height: Val::Px(200.),
+                flex_direction: FlexDirection::Column,
+                overflow: Overflow::clip_y(),
+                ..Default::default()
+            },
+            background_color: BevyColor::rgba(0., 0., 0., 0.3).into(),
+            ..default()
+        }, Interaction::default(), ChatPanel)).with_children(|parent| {
+            parent.spawn((TextBundle::from_section("", chat_text_style.clone()), ChatText));
+        });
+        parent.spawn((TextBundle::from_section("", chat_text_style.clone()), ChatInput));
+    });
+
+    spawn_game(&mut commands, game);
+}
@@ -0,0 +1,125 @@
+use bevy::{prelude::*, sprite::{MaterialMesh2dBundle, Mesh2dHandle}, window::PrimaryWindow};
+
+use crate::{
+    board::{Board, LineCount},
+    bot::{Player, Players},
+    game::{Game, Move},
+    menu::AppState,
+    mouse::{cursor_from_middle, square_at},
+    tile::{Color, Selected, Tile},
+    Layout,
+    LineUI,
+    Theme
+};
+
+/// The placement under the cursor, before it is clicked.
+#[derive(Resource, Default)]
+pub struct Preview {
+    pub mv: Option<Move>,
+    /// How many more lines of each length, starting from one card, the player would have.
+    pub deltas: Vec<i64>
+}
+
+/// The ghost of the selected face and the squares it would newly block.
+#[derive(Component)]
+pub struct PreviewUI;
+
+/// Shows what placing the selected face on the square under the cursor would do:
+/// a ghost of the face, the squares it would block for the opponent that are not
+/// blocked yet and, next to the line counts, how they would change.
+pub fn preview_system(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    window: Query<&Window, With<PrimaryWindow>>,
+    state: Res<State<AppState>>,
+    players: Res<Players>,
+    layout: Res<Layout>,
+    theme: Res<Theme>,
+    line_counts: Res<LineCount>,
+    game: Query<&Game>,
+    board: Query<Ref<Board>>,
+    selected: Query<&Selected>,
+    shown: Query<Entity, With<PreviewUI>>,
+    mut preview: ResMut<Preview>,
+) {
+    let b = board.single();
+    let g = game.single();
+    let live = g.get_pos() + 1 == g.get_history().len();
+    // the same moves a click would play
+    let mv = match (selected.single().face, window.get_single().ok().and_then(cursor_from_middle)) {
+        (Some(face), Some(pos)) if *state.get() == AppState::InGame
+            && players.get(b.color) == Player::Human
+            && (live || !players.has_remote()) =>
+        {
+            square_at(&layout, pos).map(|(x, y)| Move {x, y, face})
+        },
+        _ => None
+    };
+    let placed = mv.and_then(|mv| {
+        let mut placed = b.clone();
+        return placed.place_tile(mv.x, mv.y, mv.face).is_ok().then_some(placed);
+    });
+    let mv = mv.filter(|_| placed.is_some());
+    if mv == preview.mv && !b.is_changed() && !layout.is_changed() {
+        return;
+    }
+
+    for entity in &shown {
+        commands.entity(entity).despawn();
+    }
+    preview.mv = mv;
+    preview.deltas.clear();
+    let (Some(mv), Some(placed)) = (mv, placed) else {
+        return;
+    };
+
+    let i = (b.color == Color::Red) as usize;
+    let after = placed.get_lines();
+    preview.deltas = after[i].iter().zip(&line_counts.0[i]).map(|(after, before)| *after as i64 - *before as i64).collect();
+
+    let (size_x, size_y) = placed.get_size();
+    for (x, y) in (0..size_y).flat_map(|y| (0..size_x).map(move |x| (x, y))) {
+        if !matches!(placed.get_tile(x, y), Ok(Tile::Blocked)) || matches!(b.get_tile(x, y), Ok(Tile::Blocked)) {
+            continue;
+        }
+        let position = layout.square_position(x, y);
+        commands.spawn((MaterialMesh2dBundle {
+            mesh: Mesh2dHandle(meshes.add(Circle::new(layout.block_size))),
+            material: materials.add(theme.blocked.with_a(0.5)),
+            transform: Transform::from_xyz(position.x, position.y, 2.),
+            ..default()
+        }, PreviewUI));
+    }
+
+    let position = layout.square_position(mv.x, mv.y);
+    commands.spawn((MaterialMesh2dBundle {
+        mesh: Mesh2dHandle(meshes.add(Rectangle::new(layout.card_size, layout.card_size))),
+        material: materials.add(theme.color(b.color).with_a(0.5)),
+        transform: Transform::from_xyz(position.x, position.y, 2.),
+        ..default()
+    }, PreviewUI));
+    commands.spawn((Text2dBundle {
+        text: Text::from_section(mv.face.to_char().to_string(), TextStyle {
+            font: default(),
+            font_size: layout.font_size,
+            color: theme.card_text.with_a(0.5)
+        }),
+        transform: Transform::from_xyz(position.x, position.y, 3.),
+        ..default()
+    }, PreviewUI));
+}
+
+/// Adds the change the preview would make to the line counts of the player to move.
+pub fn update_preview_counts(
+    preview: Res<Preview>,
+    board: Query<&Board>,
+    mut line_text: Query<(&mut Text, &LineUI, &Color)>,
+) {
+    let color = board.single().color;
+    for (mut text, LineUI(i), _) in line_text.iter_mut().filter(|entry| *entry.2 == color) {
+        if let Some(delta) = preview.deltas.get(*i).filter(|delta| **delta != 0) {
+            text.sections[0].value.push_str(&format!(" ({delta:+})"));
+        }
+    }
+}
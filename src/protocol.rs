@@ -0,0 +1,161 @@
+use std::{collections::hash_map::RandomState, hash::{BuildHasher, Hasher}, time::{SystemTime, UNIX_EPOCH}};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{game::{EndReason, Game, Offer}, tile::{Color, Face, Tile}};
+
+/// The version of the game server protocol, sent with every message.
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// A message on the wire, flattened into one JSON object with the protocol version,
+/// e.g. `{"v":1,"type":"join","room":"club"}`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Versioned<T> {
+    pub v: u32,
+    #[serde(flatten)]
+    pub message: T
+}
+
+impl<T> Versioned<T> {
+    pub fn new(message: T) -> Versioned<T> {
+        Versioned {v: PROTOCOL_VERSION, message}
+    }
+}
+
+/// Messages sent by clients of the game server,
+/// e.g. `{"type":"join","room":"club"}`, `{"type":"watch","room":"club"}` or `{"type":"move","move":"Kd4"}`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ClientMessage {
+    Join {
+        room: String
+    },
+    /// Joins a room without taking a seat, to follow the game read-only.
+    Watch {
+        room: String
+    },
+    /// Takes back the seat left in a game, e.g. after the connection dropped.
+    Resume {
+        game_id: u64,
+        token: String
+    },
+    Move {
+        #[serde(rename = "move")]
+        mv: String
+    },
+    /// Says something to everyone in the room.
+    Chat {
+        text: String
+    },
+    Resign,
+    /// Offers a draw or asks to take back the last move, which the opponent has to accept.
+    Offer {
+        offer: Offer
+    },
+    /// Accepts or declines the opponent's offer.
+    Answer {
+        accept: bool
+    },
+    Leave
+}
+
+/// Messages sent by the game server.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ServerMessage {
+    /// `game_id` and `token` are what it takes to resume the seat.
+    Joined {
+        room: String,
+        color: Color,
+        game_id: u64,
+        token: String
+    },
+    Watching {
+        room: String
+    },
+    /// The whole game after every move.  `board` has one string per row from the top,
+    /// with `.` for empty, `#` for blocked, `W` for the Wild and upper case for Red's
+    /// cards and lower case for Black's.  Pieces left are counted as Aces, Kings, Queens, Jacks.
+    State {
+        room: String,
+        moves: Vec<String>,
+        to_move: Color,
+        red: [u32; 4],
+        black: [u32; 4],
+        board: Vec<String>
+    },
+    Result {
+        room: String,
+        winner: Option<Color>,
+        reason: EndReason
+    },
+    Offered {
+        room: String,
+        from: Color,
+        offer: Offer
+    },
+    Declined {
+        room: String
+    },
+    /// `from` is the color of the player who said it, or nothing for a spectator.
+    /// `speaker` is `red`, `black` or `spectator-<n>`, different for every spectator.
+    Chat {
+        room: String,
+        from: Option<Color>,
+        speaker: String,
+        text: String
+    },
+    /// A player's connection dropped and the seat is held for them until `forfeit_in_secs`.
+    Away {
+        room: String,
+        color: Color,
+        forfeit_in_secs: u64
+    },
+    /// A player who was away took their seat back.
+    Back {
+        room: String,
+        color: Color
+    },
+    Error {
+        message: String
+    }
+}
+
+impl ServerMessage {
+    pub fn state(room: &str, game: &Game) -> ServerMessage {
+        let (_, board, (red_inv, black_inv)) = game.get_history().last().unwrap();
+        let (size_x, size_y) = board.get_size();
+        let faces = [Face::Ace, Face::King, Face::Queen, Face::Jack];
+
+        ServerMessage::State {
+            room: room.to_string(),
+            moves: game.get_history().iter()
+                .skip(1)
+                .map(|(mv, ..)| mv.unwrap().to_string())
+                .collect(),
+            to_move: board.color,
+            red: faces.map(|face| red_inv.get_num_face(face)),
+            black: faces.map(|face| black_inv.get_num_face(face)),
+            board: (0..size_y).rev()
+                .map(|y| (0..size_x).map(|x| match board.get_tile(x, y).ok().unwrap() {
+                    Tile::None => '.',
+                    Tile::Blocked => '#',
+                    Tile::Card(face, Color::Black) => face.to_char().to_ascii_lowercase(),
+                    Tile::Card(face, _) => face.to_char()
+                }).collect())
+                .collect()
+        }
+    }
+}
+
+/// A hard to guess number, for game ids and resume tokens.
+pub fn random_id() -> u64 {
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u128(SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_nanos()));
+    return hasher.finish();
+}
+
+/// A hard to guess token for resuming a seat in a game.
+pub fn new_token() -> String {
+    return format!("{:016x}{:016x}", random_id(), random_id());
+}
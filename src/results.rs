@@ -0,0 +1,81 @@
+use std::{fs, io, path::PathBuf, time::{SystemTime, UNIX_EPOCH}};
+
+use bevy::prelude::*;
+
+use crate::{game::{EndReason, Game}, Theme};
+
+/// Where finished games are saved from the game-over screen.
+pub const RECORDS_DIR: &str = "records";
+
+/// Says where the game's record was saved, or why it was not.
+#[derive(Component)]
+pub struct SavedText;
+
+/// The first place where the line lengths, longest first, differ, which decided
+/// the game on lines.  `None` for a draw.
+pub fn deciding_line(lengths: &[Vec<usize>; 2]) -> Option<usize> {
+    let rows = lengths[0].len().max(lengths[1].len());
+    return (0..rows).find(|i| lengths[0].get(*i) != lengths[1].get(*i));
+}
+
+/// Both players' line lengths on the final board side by side, Red on the left,
+/// highlighting the pair that decided the game if the lines decided it.
+pub fn spawn_line_table(commands: &mut Commands, theme: &Theme, game: &Game) -> Entity {
+    let board = &game.get_history().last().unwrap().1;
+    let lengths = board.get_line_lengths();
+    let decided = match game.get_result() {
+        Some(result) if result.reason == EndReason::Lines => deciding_line(&lengths),
+        _ => None
+    };
+    let rows = lengths[0].len().max(lengths[1].len());
+    let style = |color| TextStyle {
+        font: default(),
+        font_size: 22.,
+        color
+    };
+
+    return commands.spawn(NodeBundle {
+        style: Style {
+            flex_direction: FlexDirection::Column,
+            max_height: Val::Percent(40.),
+            overflow: Overflow::clip_y(),
+            margin: UiRect::bottom(Val::Px(20.)),
+            ..Default::default()
+        },
+        ..default()
+    }).with_children(|parent| {
+        let mut row = |red: String, black: String, background: Color| {
+            parent.spawn(NodeBundle {
+                style: Style {
+                    column_gap: Val::Px(40.),
+                    padding: UiRect::horizontal(Val::Px(10.)),
+                    ..Default::default()
+                },
+                background_color: background.into(),
+                ..default()
+            }).with_children(|parent| {
+                for (text, color) in [(red, theme.red), (black, theme.black)] {
+                    parent.spawn(TextBundle::from_section(text, style(color)).with_style(Style {
+                        width: Val::Px(80.),
+                        ..Default::default()
+                    }));
+                }
+            });
+        };
+        row("Red".to_string(), "Black".to_string(), Color::NONE);
+        for i in 0..rows {
+            let length = |color: usize| lengths[color].get(i).map_or("-".to_string(), usize::to_string);
+            let background = if decided == Some(i) {theme.highlight.with_a(0.4)} else {Color::NONE};
+            row(length(1), length(0), background);
+        }
+    }).id();
+}
+
+/// Saves the game's record in `RECORDS_DIR`, named after the time it was saved.
+pub fn save_record(game: &Game) -> io::Result<PathBuf> {
+    fs::create_dir_all(RECORDS_DIR)?;
+    let secs = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |time| time.as_secs());
+    let path = PathBuf::from(RECORDS_DIR).join(format!("{secs}.txt"));
+    game.save(&path)?;
+    return Ok(path);
+}
@@ -0,0 +1,326 @@
+use std::{fs, io, path::Path, sync::{atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering}, Mutex}, thread, time::{Duration, Instant}};
+
+use bevy::ecs::system::Resource;
+
+use crate::{board::{Board, Inventory}, eval::Evaluator, game::Move, tile::{Color, Face}};
+
+/// Search settings, loaded from a file of `name = value` lines:
+/// `max_depth`, `time_limit_ms`, `threads` and `table_size` (entries in the transposition table).
+#[derive(Resource, Clone, Debug)]
+pub struct SearchConfig {
+    pub max_depth: u32,
+    pub time_limit: Duration,
+    pub threads: usize,
+    pub table_size: usize
+}
+
+impl Default for SearchConfig {
+    fn default() -> SearchConfig {
+        SearchConfig {
+            max_depth: 6,
+            time_limit: Duration::from_secs(3),
+            threads: thread::available_parallelism().map_or(1, |n| n.get()),
+            table_size: 1 << 20
+        }
+    }
+}
+
+impl SearchConfig {
+    pub fn load(path: impl AsRef<Path>) -> io::Result<SearchConfig> {
+        let mut config = SearchConfig::default();
+        for line in fs::read_to_string(path)?.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let invalid = || io::Error::new(io::ErrorKind::InvalidData, format!("invalid setting: {line}"));
+
+            let (name, value) = line.split_once('=').ok_or_else(invalid)?;
+            let value: u64 = value.trim().parse().map_err(|_| invalid())?;
+            match name.trim() {
+                "max_depth" => config.max_depth = value as u32,
+                "time_limit_ms" => config.time_limit = Duration::from_millis(value),
+                "threads" => config.threads = (value as usize).max(1),
+                "table_size" => config.table_size = (value as usize).max(1),
+                _ => return Err(invalid())
+            }
+        }
+        return Ok(config);
+    }
+}
+
+/// Shared between a running search and whoever is waiting on it.
+/// The search keeps it up to date and stops as soon as it is cancelled.
+#[derive(Default)]
+pub struct SearchProgress {
+    depth: AtomicU32,
+    nodes: AtomicU64,
+    best: Mutex<Option<Move>>,
+    cancelled: AtomicBool
+}
+
+impl SearchProgress {
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        return self.cancelled.load(Ordering::Relaxed);
+    }
+
+    /// The deepest search completed so far.
+    pub fn get_depth(&self) -> u32 {
+        return self.depth.load(Ordering::Relaxed);
+    }
+
+    /// Nodes searched so far by all threads.
+    pub fn get_nodes(&self) -> u64 {
+        return self.nodes.load(Ordering::Relaxed);
+    }
+
+    /// The best move of the deepest search completed so far.
+    pub fn get_best(&self) -> Option<Move> {
+        return *self.best.lock().unwrap();
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Bound {
+    Exact = 0,
+    Lower = 1,
+    Upper = 2
+}
+
+#[derive(Clone, Copy)]
+struct Entry {
+    score: f32,
+    depth: u32,
+    bound: Bound,
+    best: Option<Move>
+}
+
+/// Transposition table shared by every search thread without locking.
+/// Each slot stores the key xor'd with the data, so a slot torn by two threads
+/// writing at once fails the key check instead of returning the wrong entry.
+struct TranspositionTable {
+    slots: Vec<(AtomicU64, AtomicU64)>
+}
+
+impl TranspositionTable {
+    fn new(size: usize) -> TranspositionTable {
+        TranspositionTable {
+            slots: (0..size).map(|_| (AtomicU64::new(0), AtomicU64::new(0))).collect()
+        }
+    }
+
+    fn probe(&self, hash: u64) -> Option<Entry> {
+        let (key, data) = &self.slots[(hash % self.slots.len() as u64) as usize];
+        let data = data.load(Ordering::Relaxed);
+        if key.load(Ordering::Relaxed) ^ data != hash || data == 0 {
+            return None;
+        }
+
+        let best = if (data >> 42) & 1 == 1 {
+            Some(Move {
+                x: ((data >> 43) & 0x3f) as u32,
+                y: ((data >> 49) & 0x3f) as u32,
+                face: Face::from_num(((data >> 55) & 0b11) as u32)
+            })
+        } else {
+            None
+        };
+        return Some(Entry {
+            score: f32::from_bits(data as u32),
+            depth: ((data >> 32) & 0xff) as u32,
+            bound: match (data >> 40) & 0b11 {
+                0 => Bound::Exact,
+                1 => Bound::Lower,
+                _ => Bound::Upper
+            },
+            best
+        });
+    }
+
+    fn store(&self, hash: u64, entry: Entry) {
+        let mut data = entry.score.to_bits() as u64
+            | (entry.depth.min(0xff) as u64) << 32
+            | (entry.bound as u64) << 40;
+        if let Some(mv) = entry.best {
+            data |= 1 << 42
+                | (mv.x as u64 & 0x3f) << 43
+                | (mv.y as u64 & 0x3f) << 49
+                | (mv.face as u64 & 0b11) << 55;
+        }
+
+        let (key, slot) = &self.slots[(hash % self.slots.len() as u64) as usize];
+        key.store(hash ^ data, Ordering::Relaxed);
+        slot.store(data, Ordering::Relaxed);
+    }
+}
+
+struct Searcher<'a> {
+    evaluator: &'a Evaluator,
+    progress: &'a SearchProgress,
+    table: &'a TranspositionTable,
+    stop: &'a AtomicBool,
+    deadline: Instant
+}
+
+/// Iterative deepening alpha-beta search for the player to move, run on
+/// `config.threads` threads that share one transposition table (lazy SMP).
+/// Returns the best move of the deepest search that finished, or `None`
+/// if the player has no move or the search was cancelled.
+pub fn search(
+    board: &Board, red_inv: &Inventory, black_inv: &Inventory,
+    evaluator: &Evaluator, config: &SearchConfig, progress: &SearchProgress
+) -> Option<Move> {
+    let inv = if board.color == Color::Red {red_inv} else {black_inv};
+    let moves = board.get_legal_moves(inv);
+    // fall back on any legal move if not even the first depth finishes in time
+    *progress.best.lock().unwrap() = moves.first().copied();
+    if moves.len() <= 1 {
+        return moves.first().copied();
+    }
+
+    let table = TranspositionTable::new(config.table_size);
+    let stop = AtomicBool::new(false);
+    let searcher = Searcher {
+        evaluator,
+        progress,
+        table: &table,
+        stop: &stop,
+        deadline: Instant::now() + config.time_limit
+    };
+
+    thread::scope(|scope| {
+        for helper in 1..config.threads {
+            let searcher = &searcher;
+            let mut helper_moves = moves.clone();
+            // helpers start from different moves so they fill the table with different lines
+            helper_moves.rotate_left(helper % moves.len());
+            scope.spawn(move || searcher.iterate(board, red_inv, black_inv, helper_moves, config.max_depth, false));
+        }
+        searcher.iterate(board, red_inv, black_inv, moves, config.max_depth, true);
+        stop.store(true, Ordering::Relaxed);
+    });
+
+    return progress.get_best().filter(|_| !progress.is_cancelled());
+}
+
+impl Searcher<'_> {
+    /// Searches the root moves one depth deeper each time.  Only the main
+    /// thread reports its results; the helpers are there to fill the table.
+    fn iterate(
+        &self, board: &Board, red_inv: &Inventory, black_inv: &Inventory,
+        mut moves: Vec<Move>, max_depth: u32, main: bool
+    ) {
+        for depth in 1..=max_depth {
+            let mut best = None;
+            let mut alpha = f32::NEG_INFINITY;
+            for (i, mv) in moves.iter().enumerate() {
+                let (b, red, black) = make_move(board, red_inv, black_inv, *mv);
+                let Some(score) = self.negamax(&b, &red, &black, depth - 1, f32::NEG_INFINITY, -alpha) else {
+                    return;
+                };
+                if best.is_none() || -score > alpha {
+                    alpha = -score;
+                    best = Some(i);
+                }
+            }
+
+            let Some(i) = best else {
+                return;
+            };
+            // search the best move first next time, the cutoffs depend on it
+            let mv = moves.remove(i);
+            moves.insert(0, mv);
+
+            if main {
+                *self.progress.best.lock().unwrap() = Some(mv);
+                self.progress.depth.store(depth, Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// Returns the score for the player to move, or `None` if the search has to stop.
+    fn negamax(
+        &self, board: &Board, red_inv: &Inventory, black_inv: &Inventory,
+        depth: u32, mut alpha: f32, mut beta: f32
+    ) -> Option<f32> {
+        let nodes = self.progress.nodes.fetch_add(1, Ordering::Relaxed);
+        if self.progress.is_cancelled() || self.stop.load(Ordering::Relaxed)
+        || (nodes % 1024 == 0 && Instant::now() > self.deadline)
+        {
+            self.stop.store(true, Ordering::Relaxed);
+            return None;
+        }
+
+        let sign = if board.color == Color::Red {1.} else {-1.};
+        let inv = if board.color == Color::Red {red_inv} else {black_inv};
+        if depth == 0 || inv.is_empty() {
+            return Some(sign * self.evaluator.evaluate(board, red_inv, black_inv));
+        }
+
+        let hash = board.position_hash(red_inv, black_inv);
+        let mut table_move = None;
+        if let Some(entry) = self.table.probe(hash) {
+            if entry.depth >= depth {
+                match entry.bound {
+                    Bound::Exact => return Some(entry.score),
+                    Bound::Lower => alpha = alpha.max(entry.score),
+                    Bound::Upper => beta = beta.min(entry.score)
+                }
+                if alpha >= beta {
+                    return Some(entry.score);
+                }
+            }
+            table_move = entry.best;
+        }
+
+        let mut moves = board.get_legal_moves(inv);
+        if moves.is_empty() {
+            return Some(sign * self.evaluator.evaluate(board, red_inv, black_inv));
+        }
+        if let Some(i) = table_move.and_then(|mv| moves.iter().position(|m| *m == mv)) {
+            moves.swap(0, i);
+        }
+
+        let original_alpha = alpha;
+        let mut best = (f32::NEG_INFINITY, None);
+        for mv in moves {
+            let (b, red, black) = make_move(board, red_inv, black_inv, mv);
+            let score = -self.negamax(&b, &red, &black, depth - 1, -beta, -alpha)?;
+            if score > best.0 {
+                best = (score, Some(mv));
+            }
+            alpha = alpha.max(score);
+            if alpha >= beta {
+                break;
+            }
+        }
+
+        let bound = if best.0 <= original_alpha {
+            Bound::Upper
+        } else if best.0 >= beta {
+            Bound::Lower
+        } else {
+            Bound::Exact
+        };
+        self.table.store(hash, Entry {score: best.0, depth, bound, best: best.1});
+        return Some(best.0);
+    }
+}
+
+fn make_move(board: &Board, red_inv: &Inventory, black_inv: &Inventory, mv: Move) -> (Board, Inventory, Inventory) {
+    let mut board = board.clone();
+    let mut red_inv = red_inv.clone();
+    let mut black_inv = black_inv.clone();
+
+    if board.color == Color::Red {
+        red_inv.place_face(mv.face);
+    } else {
+        black_inv.place_face(mv.face);
+    }
+    board.place_tile(mv.x, mv.y, mv.face).ok().unwrap();
+    return (board, red_inv, black_inv);
+}
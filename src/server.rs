@@ -0,0 +1,662 @@
+use std::{
+    collections::HashMap,
+    io::{BufRead, BufReader, ErrorKind, Write},
+    net::{TcpListener, TcpStream},
+    sync::{mpsc::{channel, Receiver, RecvTimeoutError, Sender}, Arc, Mutex},
+    thread,
+    time::{Duration, Instant}
+};
+
+use tungstenite::Message;
+
+use crate::{
+    game::{EndReason, Game, GameResult, Move, Offer, Ruleset},
+    protocol::{new_token, ClientMessage, ServerMessage, Versioned, PROTOCOL_VERSION},
+    tile::Color
+};
+
+pub const DEFAULT_SERVER_ADDRESS: &str = "127.0.0.1:7879";
+pub const DEFAULT_WEBSOCKET_ADDRESS: &str = "127.0.0.1:7880";
+pub const DEFAULT_FORFEIT_TIMEOUT: Duration = Duration::from_secs(60);
+/// The longest chat message, in characters.
+pub const MAX_CHAT_LEN: usize = 500;
+/// How long a WebSocket client's reader waits before checking for messages to send.
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+pub type ClientId = u64;
+
+struct Client {
+    sender: Sender<ServerMessage>,
+    room: Option<String>
+}
+
+struct Seat {
+    client: Option<ClientId>,
+    token: String,
+    /// When the player's connection dropped, while the seat is held for them.
+    away: Option<Instant>
+}
+
+struct Room {
+    id: u64,
+    game: Game,
+    red: Option<Seat>,
+    black: Option<Seat>,
+    spectators: Vec<ClientId>,
+    /// An offer waiting for the other player's answer, and who made it.
+    offer: Option<(Color, Offer)>
+}
+
+impl Room {
+    fn new(id: u64) -> Room {
+        Room {
+            id,
+            game: Game::new(Ruleset::default()),
+            red: None,
+            black: None,
+            spectators: Vec::new(),
+            offer: None
+        }
+    }
+
+    fn get_seat(&mut self, color: Color) -> &mut Option<Seat> {
+        match color {
+            Color::Black => &mut self.black,
+            _ => &mut self.red
+        }
+    }
+
+    fn get_color(&self, id: ClientId) -> Option<Color> {
+        if self.red.as_ref().is_some_and(|seat| seat.client == Some(id)) {
+            return Some(Color::Red);
+        } else if self.black.as_ref().is_some_and(|seat| seat.client == Some(id)) {
+            return Some(Color::Black);
+        }
+        return None;
+    }
+
+    fn is_over(&self) -> bool {
+        return self.game.is_over();
+    }
+
+    /// Nobody is in the room, or held seats once the game is over and nobody comes back for them.
+    fn is_empty(&self) -> bool {
+        let left = |seat: &Option<Seat>| seat.as_ref().is_none_or(|seat| seat.client.is_none() && self.is_over());
+        return left(&self.red) && left(&self.black) && self.spectators.is_empty();
+    }
+}
+
+/// Hosts any number of games in named rooms.  The first two clients to join a room
+/// play Red and Black and anyone can watch; every move is checked against the
+/// room's `Game` before it is sent to everyone in the room.
+///
+/// Every game has an id and every player a token.  When a player's connection drops
+/// their seat is held, and they can take it back with the id and token until the
+/// forfeit timeout runs out and the game goes to their opponent.
+///
+/// The server does not know about transports: each client gets an id and a channel
+/// of messages, and whatever carries the messages calls `handle` with what it receives.
+pub struct Server {
+    rooms: HashMap<String, Room>,
+    clients: HashMap<ClientId, Client>,
+    next_id: ClientId,
+    next_game: u64,
+    forfeit_timeout: Duration
+}
+
+impl Default for Server {
+    fn default() -> Server {
+        return Server::new(DEFAULT_FORFEIT_TIMEOUT);
+    }
+}
+
+impl Server {
+    pub fn new(forfeit_timeout: Duration) -> Server {
+        Server {
+            rooms: HashMap::new(),
+            clients: HashMap::new(),
+            next_id: 0,
+            next_game: 1,
+            forfeit_timeout
+        }
+    }
+
+    pub fn connect(&mut self) -> (ClientId, Receiver<ServerMessage>) {
+        let (sender, receiver) = channel();
+        let id = self.next_id;
+        self.next_id += 1;
+        self.clients.insert(id, Client {sender, room: None});
+        return (id, receiver);
+    }
+
+    pub fn disconnect(&mut self, id: ClientId) {
+        self.leave(id);
+        self.clients.remove(&id);
+    }
+
+    pub fn handle(&mut self, id: ClientId, message: ClientMessage) {
+        match message {
+            ClientMessage::Join {room} => self.join(id, room),
+            ClientMessage::Watch {room} => self.watch(id, room),
+            ClientMessage::Resume {game_id, token} => self.resume(id, game_id, &token),
+            ClientMessage::Move {mv} => self.play(id, &mv),
+            ClientMessage::Chat {text} => self.chat(id, text),
+            ClientMessage::Resign => self.resign(id),
+            ClientMessage::Offer {offer} => self.offer(id, offer),
+            ClientMessage::Answer {accept} => self.answer(id, accept),
+            ClientMessage::Leave => self.leave(id)
+        }
+    }
+
+    fn send(&self, id: ClientId, message: ServerMessage) {
+        if let Some(client) = self.clients.get(&id) {
+            let _ = client.sender.send(message);
+        }
+    }
+
+    fn send_error(&self, id: ClientId, message: &str) {
+        self.send(id, ServerMessage::Error {message: message.to_string()});
+    }
+
+    fn broadcast(&self, room: &str, message: ServerMessage) {
+        let Some(r) = self.rooms.get(room) else {
+            return;
+        };
+        let players = [&r.red, &r.black].into_iter().flatten().filter_map(|seat| seat.client);
+        for id in players.chain(r.spectators.iter().copied()) {
+            self.send(id, message.clone());
+        }
+    }
+
+    fn join(&mut self, id: ClientId, room: String) {
+        if self.clients.get(&id).is_some_and(|c| c.room.is_some()) {
+            return self.send_error(id, "already in a room");
+        }
+
+        let next_game = &mut self.next_game;
+        let r = self.rooms.entry(room.clone()).or_insert_with(|| {
+            *next_game += 1;
+            Room::new(*next_game - 1)
+        });
+        let color = if r.red.is_none() {
+            Color::Red
+        } else if r.black.is_none() {
+            Color::Black
+        } else {
+            return self.send_error(id, "room is full");
+        };
+        let token = new_token();
+        *r.get_seat(color) = Some(Seat {client: Some(id), token: token.clone(), away: None});
+        let game_id = r.id;
+
+        self.clients.get_mut(&id).unwrap().room = Some(room.clone());
+        self.send(id, ServerMessage::Joined {room: room.clone(), color, game_id, token});
+        let state = ServerMessage::state(&room, &self.rooms[&room].game);
+        self.send(id, state);
+    }
+
+    fn watch(&mut self, id: ClientId, room: String) {
+        if self.clients.get(&id).is_some_and(|c| c.room.is_some()) {
+            return self.send_error(id, "already in a room");
+        }
+
+        let next_game = &mut self.next_game;
+        let r = self.rooms.entry(room.clone()).or_insert_with(|| {
+            *next_game += 1;
+            Room::new(*next_game - 1)
+        });
+        r.spectators.push(id);
+        self.clients.get_mut(&id).unwrap().room = Some(room.clone());
+        self.send(id, ServerMessage::Watching {room: room.clone()});
+        let state = ServerMessage::state(&room, &self.rooms[&room].game);
+        self.send(id, state);
+    }
+
+    fn resume(&mut self, id: ClientId, game_id: u64, token: &str) {
+        if self.clients.get(&id).is_some_and(|c| c.room.is_some()) {
+            return self.send_error(id, "already in a room");
+        }
+        let Some((room, r)) = self.rooms.iter_mut().find(|(_, r)| r.id == game_id) else {
+            return self.send_error(id, "no such game");
+        };
+        let room = room.clone();
+        let Some(color) = [Color::Red, Color::Black].into_iter()
+            .find(|color| r.get_seat(*color).as_ref().is_some_and(|seat| seat.token == token))
+        else {
+            return self.send_error(id, "invalid resume token");
+        };
+
+        // the old connection may not have been noticed as dropped yet
+        let seat = r.get_seat(color).as_mut().unwrap();
+        let old = seat.client.replace(id);
+        seat.away = None;
+        if let Some(client) = old.and_then(|old| self.clients.get_mut(&old)) {
+            client.room = None;
+        }
+
+        self.clients.get_mut(&id).unwrap().room = Some(room.clone());
+        self.send(id, ServerMessage::Joined {room: room.clone(), color, game_id, token: token.to_string()});
+        let state = ServerMessage::state(&room, &self.rooms[&room].game);
+        self.send(id, state);
+        self.broadcast(&room, ServerMessage::Back {room: room.clone(), color});
+    }
+
+    fn play(&mut self, id: ClientId, mv: &str) {
+        let Some(room) = self.clients.get(&id).and_then(|c| c.room.clone()) else {
+            return self.send_error(id, "not in a room");
+        };
+        let r = self.rooms.get_mut(&room).unwrap();
+        let Some(color) = r.get_color(id) else {
+            return self.send_error(id, "not playing in this room");
+        };
+        if r.is_over() {
+            return self.send_error(id, "game is over");
+        }
+        if r.game.get_move().1.color != color {
+            return self.send_error(id, "not your turn");
+        }
+        let Ok(mv) = mv.parse::<Move>() else {
+            return self.send_error(id, "invalid move notation");
+        };
+        if let Err(e) = r.game.play(mv) {
+            return self.send_error(id, &format!("illegal move: {}", e.describe()));
+        }
+        // a move turns down any offer
+        r.offer = None;
+
+        let state = ServerMessage::state(&room, &r.game);
+        self.broadcast(&room, state);
+        self.broadcast_result(&room);
+    }
+
+    fn broadcast_result(&self, room: &str) {
+        if let Some(result) = self.rooms[room].game.get_result() {
+            self.broadcast(room, ServerMessage::Result {
+                room: room.to_string(),
+                winner: result.winner,
+                reason: result.reason
+            });
+        }
+    }
+
+    /// The room and color of a player in a game still going on.
+    fn get_player(&self, id: ClientId) -> Result<(String, Color), &'static str> {
+        let room = self.clients.get(&id).and_then(|c| c.room.clone()).ok_or("not in a room")?;
+        let r = &self.rooms[&room];
+        let color = r.get_color(id).ok_or("not playing in this room")?;
+        if r.is_over() {
+            return Err("game is over");
+        }
+        return Ok((room, color));
+    }
+
+    fn resign(&mut self, id: ClientId) {
+        let (room, color) = match self.get_player(id) {
+            Ok(player) => player,
+            Err(e) => return self.send_error(id, e)
+        };
+        let r = self.rooms.get_mut(&room).unwrap();
+        r.game.end(GameResult {winner: Some(color.opponent()), reason: EndReason::Resignation});
+        self.broadcast_result(&room);
+    }
+
+    fn offer(&mut self, id: ClientId, offer: Offer) {
+        let (room, from) = match self.get_player(id) {
+            Ok(player) => player,
+            Err(e) => return self.send_error(id, e)
+        };
+        self.rooms.get_mut(&room).unwrap().offer = Some((from, offer));
+        self.broadcast(&room, ServerMessage::Offered {room: room.clone(), from, offer});
+    }
+
+    fn answer(&mut self, id: ClientId, accept: bool) {
+        let (room, color) = match self.get_player(id) {
+            Ok(player) => player,
+            Err(e) => return self.send_error(id, e)
+        };
+        let r = self.rooms.get_mut(&room).unwrap();
+        let Some((from, offer)) = r.offer.filter(|(from, _)| *from != color) else {
+            return self.send_error(id, "nothing to answer");
+        };
+        r.offer = None;
+        if !accept {
+            return self.broadcast(&room, ServerMessage::Declined {room: room.clone()});
+        }
+        if !r.game.accept(offer, from) {
+            return self.send_error(id, "the offer can no longer be accepted");
+        }
+
+        let state = ServerMessage::state(&room, &r.game);
+        self.broadcast(&room, state);
+        self.broadcast_result(&room);
+    }
+
+    fn chat(&mut self, id: ClientId, text: String) {
+        let Some(room) = self.clients.get(&id).and_then(|c| c.room.clone()) else {
+            return self.send_error(id, "not in a room");
+        };
+        if text.chars().count() > MAX_CHAT_LEN {
+            return self.send_error(id, "chat message too long");
+        }
+        let r = self.rooms.get_mut(&room).unwrap();
+        let from = r.get_color(id);
+        // kept with the move being played, so it ends up in the game's record
+        let speaker = match from {
+            Some(Color::Black) => "black".to_string(),
+            Some(_) => "red".to_string(),
+            None => format!("spectator-{id}")
+        };
+        r.game.comment(format!("{speaker}: {text}"));
+        self.broadcast(&room, ServerMessage::Chat {room: room.clone(), from, speaker, text});
+    }
+
+    fn leave(&mut self, id: ClientId) {
+        let Some(room) = self.clients.get_mut(&id).and_then(|c| c.room.take()) else {
+            return;
+        };
+        let Some(r) = self.rooms.get_mut(&room) else {
+            return;
+        };
+        r.spectators.retain(|spectator| *spectator != id);
+        let Some(color) = r.get_color(id) else {
+            if r.is_empty() {
+                self.rooms.remove(&room);
+            }
+            return;
+        };
+
+        // hold the seat of a game in progress for its player to come back to
+        let hold = r.game.get_history().len() > 1 && !r.is_over();
+        if hold {
+            let seat = r.get_seat(color).as_mut().unwrap();
+            seat.client = None;
+            seat.away = Some(Instant::now());
+        } else {
+            *r.get_seat(color) = None;
+        }
+
+        if r.is_empty() {
+            self.rooms.remove(&room);
+        } else if hold {
+            let forfeit_in_secs = self.forfeit_timeout.as_secs();
+            self.broadcast(&room, ServerMessage::Away {room: room.clone(), color, forfeit_in_secs});
+        }
+    }
+
+    /// Forfeits the games of players who have been away longer than the timeout.
+    pub fn check_timeouts(&mut self, now: Instant) {
+        let mut forfeits = Vec::new();
+        for (room, r) in &mut self.rooms {
+            if r.is_over() {
+                continue;
+            }
+            let expired = [Color::Red, Color::Black].into_iter().find(|color| {
+                r.get_seat(*color).as_ref()
+                    .and_then(|seat| seat.away)
+                    .is_some_and(|away| now.duration_since(away) >= self.forfeit_timeout)
+            });
+            if let Some(color) = expired {
+                *r.get_seat(color) = None;
+                r.game.end(GameResult {winner: Some(color.opponent()), reason: EndReason::Forfeit});
+                forfeits.push(room.clone());
+            }
+        }
+
+        for room in forfeits {
+            self.broadcast_result(&room);
+            if self.rooms[&room].is_empty() {
+                self.rooms.remove(&room);
+            }
+        }
+    }
+}
+
+/// Turns a message into the JSON sent on the wire.
+fn encode(message: ServerMessage) -> String {
+    return serde_json::to_string(&Versioned::new(message)).unwrap();
+}
+
+/// Handles one message as received on the wire.
+fn receive(server: &Mutex<Server>, id: ClientId, text: &str) {
+    let mut server = server.lock().unwrap();
+    match serde_json::from_str::<Versioned<ClientMessage>>(text) {
+        Ok(Versioned {v: PROTOCOL_VERSION, message}) => server.handle(id, message),
+        Ok(Versioned {v, ..}) => server.send_error(id, &format!("unsupported protocol version {v}")),
+        Err(e) => server.send_error(id, &format!("invalid message: {e}"))
+    }
+}
+
+/// Forfeits the games of players who stay away too long, checking every second.
+fn spawn_timeouts(server: Arc<Mutex<Server>>) {
+    thread::spawn(move || loop {
+        thread::sleep(Duration::from_secs(1));
+        server.lock().unwrap().check_timeouts(Instant::now());
+    });
+}
+
+/// Accepts TCP clients speaking the line-delimited JSON protocol until the listener fails.
+pub fn serve(server: Arc<Mutex<Server>>, listener: TcpListener) {
+    spawn_timeouts(server.clone());
+    for stream in listener.incoming() {
+        let Ok(stream) = stream else {
+            continue;
+        };
+        let server = server.clone();
+        thread::spawn(move || serve_client(server, stream));
+    }
+}
+
+fn serve_client(server: Arc<Mutex<Server>>, stream: TcpStream) {
+    let Ok(mut writer) = stream.try_clone() else {
+        return;
+    };
+    let (id, receiver) = server.lock().unwrap().connect();
+
+    thread::spawn(move || {
+        for message in receiver {
+            if writeln!(writer, "{}", encode(message)).is_err() {
+                break;
+            }
+        }
+    });
+
+    for line in BufReader::new(stream).lines() {
+        let Ok(line) = line else {
+            break;
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        receive(&server, id, &line);
+    }
+    server.lock().unwrap().disconnect(id);
+}
+
+/// Accepts WebSocket clients until the listener fails.
+/// Each text message carries one message of the same JSON protocol as over TCP.
+pub fn serve_websocket(server: Arc<Mutex<Server>>, listener: TcpListener) {
+    spawn_timeouts(server.clone());
+    for stream in listener.incoming() {
+        let Ok(stream) = stream else {
+            continue;
+        };
+        let server = server.clone();
+        thread::spawn(move || serve_websocket_client(server, stream));
+    }
+}
+
+fn serve_websocket_client(server: Arc<Mutex<Server>>, stream: TcpStream) {
+    let Ok(mut socket) = tungstenite::accept(stream) else {
+        return;
+    };
+    // stop reading now and then to send what the server has for the client
+    if socket.get_ref().set_read_timeout(Some(POLL_INTERVAL)).is_err() {
+        return;
+    }
+    let (id, receiver) = server.lock().unwrap().connect();
+
+    loop {
+        match socket.read() {
+            Ok(Message::Text(text)) => receive(&server, id, &text),
+            Ok(Message::Close(_)) => {
+                let _ = socket.flush();
+                break;
+            },
+            Ok(_) => (),
+            Err(tungstenite::Error::Io(e)) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => (),
+            Err(_) => break
+        }
+        let messages: Vec<ServerMessage> = receiver.try_iter().collect();
+        if messages.into_iter().any(|message| socket.send(Message::Text(encode(message))).is_err()) {
+            break;
+        }
+    }
+    server.lock().unwrap().disconnect(id);
+}
+
+/// A client living in the same process as the server, for tests and tools.
+pub struct LocalClient {
+    id: ClientId,
+    server: Arc<Mutex<Server>>,
+    receiver: Receiver<ServerMessage>
+}
+
+impl LocalClient {
+    pub fn new(server: Arc<Mutex<Server>>) -> LocalClient {
+        let (id, receiver) = server.lock().unwrap().connect();
+        LocalClient {id, server, receiver}
+    }
+
+    pub fn send(&self, message: ClientMessage) {
+        self.server.lock().unwrap().handle(self.id, message);
+    }
+
+    /// Waits up to `timeout` for the next message from the server.
+    pub fn recv(&self, timeout: Duration) -> Option<ServerMessage> {
+        match self.receiver.recv_timeout(timeout) {
+            Ok(message) => Some(message),
+            Err(RecvTimeoutError::Timeout | RecvTimeoutError::Disconnected) => None
+        }
+    }
+
+    pub fn try_recv(&self) -> Option<ServerMessage> {
+        return self.receiver.try_recv().ok();
+    }
+}
+
+impl Drop for LocalClient {
+    fn drop(&mut self) {
+        self.server.lock().unwrap().disconnect(self.id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn join(server: &Arc<Mutex<Server>>, room: &str) -> LocalClient {
+        let client = LocalClient::new(server.clone());
+        client.send(ClientMessage::Join {room: room.to_string()});
+        return client;
+    }
+
+    fn play(client: &LocalClient, mv: &str) {
+        client.send(ClientMessage::Move {mv: mv.to_string()});
+    }
+
+    fn messages(client: &LocalClient) -> Vec<ServerMessage> {
+        return std::iter::from_fn(|| client.try_recv()).collect();
+    }
+
+    fn is_error(messages: &[ServerMessage], text: &str) -> bool {
+        return matches!(messages, [ServerMessage::Error {message}] if message.starts_with(text));
+    }
+
+    fn moves(message: &ServerMessage) -> Vec<String> {
+        match message {
+            ServerMessage::State {moves, ..} => moves.clone(),
+            _ => panic!("not a state: {message:?}")
+        }
+    }
+
+    #[test]
+    fn first_two_play_red_and_black() {
+        let server = Arc::new(Mutex::new(Server::default()));
+        let red = join(&server, "room");
+        let black = join(&server, "room");
+        let third = join(&server, "room");
+
+        let colors = [&red, &black].map(|client| match messages(client).first() {
+            Some(ServerMessage::Joined {color, ..}) => *color,
+            other => panic!("not joined: {other:?}")
+        });
+        assert_eq!(colors, [Color::Red, Color::Black]);
+        assert!(is_error(&messages(&third), "room is full"));
+    }
+
+    #[test]
+    fn moves_are_checked_and_sent_to_everyone() {
+        let server = Arc::new(Mutex::new(Server::default()));
+        let red = join(&server, "room");
+        let black = join(&server, "room");
+        let spectator = LocalClient::new(server.clone());
+        spectator.send(ClientMessage::Watch {room: "room".to_string()});
+        for client in [&red, &black, &spectator] {
+            messages(client);
+        }
+
+        play(&black, "Kd3");
+        assert!(is_error(&messages(&black), "not your turn"));
+        play(&spectator, "Kd3");
+        assert!(is_error(&messages(&spectator), "not playing in this room"));
+        play(&red, "Kd4");
+        assert!(is_error(&messages(&red), "illegal move"));
+        play(&red, "zz");
+        assert!(is_error(&messages(&red), "invalid move notation"));
+
+        play(&red, "Kd3");
+        play(&red, "Qe4");
+        assert!(is_error(&messages(&red)[1..], "not your turn"));
+        play(&black, "Qe4");
+        for client in [&red, &black, &spectator] {
+            let states: Vec<Vec<String>> = messages(client).iter().map(moves).collect();
+            assert_eq!(states.last(), Some(&vec!["Kd3".to_string(), "Qe4".to_string()]));
+        }
+    }
+
+    #[test]
+    fn room_is_removed_when_both_players_forfeit() {
+        let server = Arc::new(Mutex::new(Server::new(Duration::ZERO)));
+        let red = join(&server, "room");
+        let black = join(&server, "room");
+        play(&red, "Kd3");
+        drop(red);
+        drop(black);
+        assert_eq!(server.lock().unwrap().rooms.len(), 1);
+
+        server.lock().unwrap().check_timeouts(Instant::now());
+        assert!(server.lock().unwrap().rooms.is_empty());
+    }
+
+    #[test]
+    fn spectators_chat_under_their_own_names() {
+        let server = Arc::new(Mutex::new(Server::default()));
+        let spectators = [0, 1].map(|_| {
+            let spectator = LocalClient::new(server.clone());
+            spectator.send(ClientMessage::Watch {room: "room".to_string()});
+            messages(&spectator);
+            return spectator;
+        });
+        for spectator in &spectators {
+            spectator.send(ClientMessage::Chat {text: "hi".to_string()});
+        }
+
+        let speakers: Vec<String> = messages(&spectators[0]).into_iter().map(|message| match message {
+            ServerMessage::Chat {from: None, speaker, ..} => speaker,
+            message => panic!("not a spectator's chat: {message:?}")
+        }).collect();
+        assert_eq!(speakers, [0, 1].map(|i| format!("spectator-{}", spectators[i].id)));
+        let comments = server.lock().unwrap().rooms["room"].game.get_comments()[0].clone();
+        assert_eq!(comments, speakers.iter().map(|speaker| format!("{speaker}: hi")).collect::<Vec<_>>());
+    }
+}
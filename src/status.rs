@@ -0,0 +1,69 @@
+use bevy::{prelude::*, window::PrimaryWindow};
+
+use crate::{
+    board::Board,
+    bot::{Player, Players},
+    events::{HistoryNavigated, MovePlayed, PlaceRejected},
+    game::{Game, Move},
+    menu::AppState,
+    mouse::{cursor_from_middle, square_at},
+    tile::Selected,
+    Layout
+};
+
+/// Why the player's last click did nothing, until a move is played or the history is navigated.
+#[derive(Resource, Default)]
+pub struct StatusBar {
+    pub message: Option<String>
+}
+
+#[derive(Component)]
+pub struct StatusUI;
+
+/// Keeps the reason the player's last move was refused.  Live requests come from
+/// bots, engines and the network, which report their own problems.
+pub fn status_system(
+    mut rejected: EventReader<PlaceRejected>,
+    mut played: EventReader<MovePlayed>,
+    mut navigated: EventReader<HistoryNavigated>,
+    mut status: ResMut<StatusBar>,
+) {
+    if played.read().count() > 0 || navigated.read().count() > 0 {
+        status.message = None;
+    }
+    for PlaceRejected {mv, live, error} in rejected.read() {
+        if !live {
+            status.message = Some(format!("{mv}: {}", error.describe()));
+        }
+    }
+}
+
+/// Shows why the selected face cannot go on the square under the cursor, or else
+/// why the last click did nothing.
+pub fn update_status_bar(
+    window: Query<&Window, With<PrimaryWindow>>,
+    state: Res<State<AppState>>,
+    players: Res<Players>,
+    layout: Res<Layout>,
+    status: Res<StatusBar>,
+    game: Query<&Game>,
+    board: Query<&Board>,
+    selected: Query<&Selected>,
+    mut panel: Query<&mut Text, With<StatusUI>>,
+) {
+    let g = game.single();
+    let live = g.get_pos() + 1 == g.get_history().len();
+    let hovered = match (selected.single().face, window.get_single().ok().and_then(cursor_from_middle)) {
+        (Some(face), Some(pos)) if *state.get() == AppState::InGame
+            && players.get(board.single().color) == Player::Human
+            && (live || !players.has_remote()) =>
+        {
+            square_at(&layout, pos).map(|(x, y)| Move {x, y, face})
+        },
+        _ => None
+    };
+    let reason = hovered.and_then(|mv| g.check(mv).err().map(|error| format!("{mv}: {}", error.describe())));
+
+    let mut text = panel.single_mut();
+    text.sections[0].value = reason.or(status.message.clone()).unwrap_or_default();
+}
@@ -1,4 +1,5 @@
 use bevy::ecs::component::Component;
+use serde::{Deserialize, Serialize};
 
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub enum Tile {
@@ -47,9 +48,30 @@ impl Face {
             _ => panic!()
         }
     }
+
+    pub fn to_char(&self) -> char {
+        match self {
+            Face::Wild => 'W',
+            Face::Ace => 'A',
+            Face::King => 'K',
+            Face::Queen => 'Q',
+            Face::Jack => 'J'
+        }
+    }
+
+    pub fn from_char(c: char) -> Option<Face> {
+        match c.to_ascii_uppercase() {
+            'A' => Some(Face::Ace),
+            'K' => Some(Face::King),
+            'Q' => Some(Face::Queen),
+            'J' => Some(Face::Jack),
+            _ => None
+        }
+    }
 }
 
-#[derive(Component, Clone, Copy, Debug, PartialEq)]
+#[derive(Component, Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum Color {
     Both, Red, Black
 }
@@ -62,6 +84,12 @@ impl Color {
             _ => *self
         };
     }
+
+    pub fn opponent(self) -> Color {
+        let mut color = self;
+        color.next();
+        return color;
+    }
 }
 
 #[derive(Component)]
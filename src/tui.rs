@@ -0,0 +1,264 @@
+use std::io::{self, Write};
+
+use crossterm::{
+    cursor,
+    event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+    execute, queue,
+    style::{Attribute, Color as TermColor, Print, PrintStyledContent, Stylize},
+    terminal::{self, ClearType}
+};
+
+use crate::{
+    board::{Board, Inventory},
+    bot::{self, Computer, Player, Players},
+    game::{Game, Move, MoveError, Position},
+    tile::{Color, Face, Tile}
+};
+
+const KEYS: &str = "arrows move, A/K/Q/J pick a face, Enter places it, F/P/N/L go through the game, Esc quits";
+
+/// A game played in a terminal, for machines without a GPU or over SSH.
+/// Human players share the keyboard.
+pub struct Tui {
+    pub game: Game,
+    players: Players,
+    computer: Computer,
+    cursor: (u32, u32),
+    selected: Option<Face>,
+    message: String
+}
+
+impl Tui {
+    pub fn new(game: Game, players: Players, computer: Computer) -> Tui {
+        let (x, y) = game.get_history()[0].1.get_size();
+        Tui {
+            game,
+            players,
+            computer,
+            cursor: (x / 2, y / 2),
+            selected: None,
+            message: String::new()
+        }
+    }
+
+    /// Handles a key press, returning false once the player quits.
+    pub fn handle(&mut self, key: KeyEvent) -> bool {
+        let (size_x, size_y) = self.game.get_move().1.get_size();
+        let (x, y) = self.cursor;
+        match key.code {
+            KeyCode::Esc => return false,
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => return false,
+            KeyCode::Left => self.cursor.0 = x.saturating_sub(1),
+            KeyCode::Right => self.cursor.0 = (x + 1).min(size_x - 1),
+            KeyCode::Up => self.cursor.1 = (y + 1).min(size_y - 1),
+            KeyCode::Down => self.cursor.1 = y.saturating_sub(1),
+            KeyCode::Enter | KeyCode::Char(' ') => self.place(),
+            KeyCode::Char(c) => match c.to_ascii_lowercase() {
+                'f' => self.navigate(Game::first_move),
+                'p' => self.navigate(Game::prev_move),
+                'n' => self.navigate(Game::next_move),
+                'l' => self.navigate(Game::last_move),
+                _ => if let Some(face) = Face::from_char(c) {
+                    let (_, b, (red_inv, black_inv)) = self.game.get_move();
+                    let inv = if b.color == Color::Red {red_inv} else {black_inv};
+                    if !inv.can_place_face(face) {
+                        self.message = MoveError::NoPieces.describe();
+                    }
+                    self.selected = Some(face).filter(|face| inv.can_place_face(*face) && self.selected != Some(*face));
+                }
+            },
+            _ => ()
+        }
+        return true;
+    }
+
+    fn navigate(&mut self, to: fn(&mut Game) -> &Board) {
+        to(&mut self.game);
+        self.selected = None;
+        self.message.clear();
+    }
+
+    fn place(&mut self) {
+        let Some(face) = self.selected else {
+            self.message = "pick a face first".to_string();
+            return;
+        };
+        if self.game.is_over() {
+            self.message = MoveError::GameOver.describe();
+            return;
+        } else if self.computer_to_move().is_some() {
+            self.message = MoveError::WrongTurn.describe();
+            return;
+        }
+        let mv = Move {x: self.cursor.0, y: self.cursor.1, face};
+        match self.game.play(mv) {
+            Ok(()) => {
+                self.selected = None;
+                self.message = format!("played {mv}");
+            },
+            Err(e) => self.message = format!("{mv} cannot be played: {}", e.describe())
+        }
+    }
+
+    /// The player to move, if it is the computer's turn in the latest position.
+    fn computer_to_move(&self) -> Option<Player> {
+        let player = self.players.get(self.game.get_move().1.color);
+        let live = self.game.get_pos() + 1 == self.game.get_history().len();
+        return Some(player).filter(|player| *player != Player::Human && live && !self.game.is_over());
+    }
+
+    /// Lets the bot or engine to move play, waiting for its move.
+    fn play_computer(&mut self, player: Player) {
+        let (_, b, (red_inv, black_inv)) = self.game.get_move().clone();
+        let color = b.color;
+        let mv = match player {
+            Player::Engine => {
+                let position = Position {board: b, red: red_inv, black: black_inv};
+                let Some(engine) = self.computer.engines.get_mut(color) else {
+                    return;
+                };
+                match engine.choose_move(&position) {
+                    Ok(mv) => Some(mv),
+                    Err(e) => {
+                        self.computer.engines.fail(color, &mut self.players, e);
+                        self.message = self.computer.engines.error.clone().unwrap();
+                        return;
+                    }
+                }
+            },
+            _ => bot::choose_move(&self.computer.book, &self.computer.evaluator, &self.computer.config, &b, &red_inv, &black_inv)
+        };
+        let Some(mv) = mv else {
+            self.players.set(color, Player::Human);
+            self.message = "the bot found no move, a human plays on".to_string();
+            return;
+        };
+        match self.game.play(mv) {
+            Ok(()) => self.message = format!("played {mv}"),
+            Err(_) if player == Player::Engine => {
+                self.computer.engines.fail(color, &mut self.players, format!("played {mv}, which is not legal"));
+                self.message = self.computer.engines.error.clone().unwrap();
+            },
+            Err(_) => {
+                self.players.set(color, Player::Human);
+                self.message = format!("the bot played {mv}, which is not legal, a human plays on");
+            }
+        }
+    }
+
+    /// The squares the selected face can be placed on.
+    fn targets(&self) -> Vec<(u32, u32)> {
+        let Some(face) = self.selected else {
+            return Vec::new();
+        };
+        let (_, b, (red_inv, black_inv)) = self.game.get_move();
+        let inv = if b.color == Color::Red {red_inv} else {black_inv};
+        return b.get_legal_moves(inv).into_iter()
+            .filter(|mv| mv.face == face)
+            .map(|mv| (mv.x, mv.y))
+            .collect();
+    }
+
+    pub fn draw(&self, out: &mut impl Write) -> io::Result<()> {
+        let (_, b, (red_inv, black_inv)) = self.game.get_move();
+        let (size_x, size_y) = b.get_size();
+        let targets = self.targets();
+        queue!(out, terminal::Clear(ClearType::All), cursor::MoveTo(0, 0))?;
+
+        let status = match self.game.get_result() {
+            Some(result) if self.game.get_pos() + 1 == self.game.get_history().len() => result.describe(),
+            _ if b.color == Color::Red => "Red to move".to_string(),
+            _ => "Black to move".to_string()
+        };
+        queue!(out, Print(format!("{status}, move {} of {}", self.game.get_pos(), self.game.get_history().len() - 1)))?;
+
+        // the first row is at the bottom, as on the board in the window
+        let mut row = 2;
+        for y in (0..size_y).rev() {
+            queue!(out, cursor::MoveTo(0, row), Print(format!("{:>2} ", y + 1)))?;
+            for x in 0..size_x {
+                let cell = match b.get_tile(x, y).unwrap() {
+                    Tile::Card(face, Color::Red) => format!(" {} ", face.to_char()).with(TermColor::Red).bold(),
+                    Tile::Card(face, Color::Black) => format!(" {} ", face.to_char()).with(TermColor::Grey).bold(),
+                    Tile::Card(face, Color::Both) => format!(" {} ", face.to_char()).with(TermColor::Magenta).bold(),
+                    Tile::Blocked => " x ".to_string().with(TermColor::Yellow),
+                    Tile::None if targets.contains(&(x, y)) => " + ".to_string().with(TermColor::Green),
+                    Tile::None => " . ".to_string().with(TermColor::DarkGrey)
+                };
+                let cell = if (x, y) == self.cursor {cell.attribute(Attribute::Reverse)} else {cell};
+                queue!(out, PrintStyledContent(cell))?;
+            }
+            row += 1;
+        }
+        let files: String = (0..size_x).map(|x| format!(" {} ", (b'a' + x as u8) as char)).collect();
+        queue!(out, cursor::MoveTo(0, row), Print(format!("   {files}")))?;
+
+        let lines = b.get_lines();
+        row += 2;
+        let players = [
+            ("Red", Color::Red, TermColor::Red, red_inv, &lines[1]),
+            ("Black", Color::Black, TermColor::Grey, black_inv, &lines[0])
+        ];
+        for (name, color, term_color, inv, lines) in players {
+            let to_move = if b.color == color {">"} else {" "};
+            let selected = self.selected.filter(|_| b.color == color);
+            queue!(
+                out, cursor::MoveTo(0, row),
+                PrintStyledContent(format!("{to_move} {name:<6}").with(term_color)),
+                Print(format!("{}  lines {}", inventory(inv, selected), line_counts(lines)))
+            )?;
+            row += 1;
+        }
+
+        row += 1;
+        queue!(out, cursor::MoveTo(0, row), Print(&self.message))?;
+        queue!(out, cursor::MoveTo(0, row + 2), PrintStyledContent(KEYS.with(TermColor::DarkGrey)))?;
+        return out.flush();
+    }
+}
+
+/// Plays `game` in the terminal until the player quits, returning it as it was left.
+pub fn run(game: Game, players: Players, computer: Computer) -> io::Result<Game> {
+    let mut out = io::stdout();
+    terminal::enable_raw_mode()?;
+    execute!(out, terminal::EnterAlternateScreen, cursor::Hide)?;
+
+    let mut tui = Tui::new(game, players, computer);
+    let result = play(&mut tui, &mut out);
+
+    // give the terminal back even if drawing failed
+    let _ = execute!(out, cursor::Show, terminal::LeaveAlternateScreen);
+    let _ = terminal::disable_raw_mode();
+    return result.map(|_| tui.game);
+}
+
+fn play(tui: &mut Tui, out: &mut impl Write) -> io::Result<()> {
+    loop {
+        if let Some(player) = tui.computer_to_move() {
+            tui.message = "thinking...".to_string();
+            tui.draw(out)?;
+            tui.play_computer(player);
+            continue;
+        }
+        tui.draw(out)?;
+        if let Event::Key(key) = event::read()? {
+            if key.kind == KeyEventKind::Press && !tui.handle(key) {
+                return Ok(());
+            }
+        }
+    }
+}
+
+fn inventory(inv: &Inventory, selected: Option<Face>) -> String {
+    let mut value = String::new();
+    for face in [Face::Ace, Face::King, Face::Queen, Face::Jack] {
+        let mark = if selected == Some(face) {'*'} else {' '};
+        value += &format!("{mark}{} {} ", face.to_char(), inv.get_num_face(face));
+    }
+    return value;
+}
+
+/// Counts of lines by length, from two cards up.
+fn line_counts(lines: &[usize]) -> String {
+    return (1..lines.len()).map(|i| format!("{}:{}", i + 1, lines[i])).collect::<Vec<_>>().join(" ");
+}
@@ -0,0 +1,98 @@
+use crate::{eval::{get_features, Evaluator, FEATURES}, game::Game, tile::Color};
+
+/// A position from a finished game, with the game's result for Red:
+/// `1` for a win, `0.5` for a draw and `0` for a loss.
+pub struct Sample {
+    pub features: [f32; FEATURES],
+    pub result: f32
+}
+
+/// Turns every unfinished position of a finished game into a sample.
+pub fn get_samples(game: &Game) -> Vec<Sample> {
+    if !game.is_over() {
+        return Vec::new();
+    }
+    let history = game.get_history();
+    let result = match game.get_result().unwrap().winner {
+        Some(Color::Red) => 1.,
+        Some(_) => 0.,
+        None => 0.5
+    };
+
+    return history[..history.len() - 1].iter()
+        .map(|(_, board, (red_inv, black_inv))| Sample {
+            features: get_features(board, red_inv, black_inv),
+            result
+        })
+        .collect();
+}
+
+fn predict(weights: &[f32; FEATURES], sample: &Sample, k: f32) -> f32 {
+    let score: f32 = sample.features.iter().zip(weights).map(|(f, w)| f * w).sum();
+    return 1. / (1. + (-k * score).exp());
+}
+
+/// Mean squared error between the predicted and actual results.
+pub fn get_error(weights: &[f32; FEATURES], samples: &[Sample], k: f32) -> f32 {
+    let total: f32 = samples.iter()
+        .map(|sample| (predict(weights, sample, k) - sample.result).powi(2))
+        .sum();
+    return total / samples.len().max(1) as f32;
+}
+
+/// Finds the scale that turns evaluations into win probabilities with the least error
+/// for the given weights.  It is kept fixed while tuning, as in Texel tuning.
+pub fn fit_scale(weights: &[f32; FEATURES], samples: &[Sample]) -> f32 {
+    let mut best = (f32::MAX, 1.);
+    let mut k = 0.001;
+    while k < 100. {
+        let error = get_error(weights, samples, k);
+        if error < best.0 {
+            best = (error, k);
+        }
+        k *= 1.05;
+    }
+    return best.1;
+}
+
+/// Fits the weights to the samples with gradient descent (Adam), starting from `start`.
+/// `report` is called every 100 iterations with the iteration and current error.
+pub fn tune(
+    start: &Evaluator, samples: &[Sample], iterations: usize,
+    mut report: impl FnMut(usize, f32)
+) -> Evaluator {
+    const LEARNING_RATE: f32 = 0.05;
+    const BETA_1: f32 = 0.9;
+    const BETA_2: f32 = 0.999;
+    const EPSILON: f32 = 1e-8;
+
+    let mut weights = start.weights;
+    let k = fit_scale(&weights, samples);
+    let mut m = [0.; FEATURES];
+    let mut v = [0.; FEATURES];
+
+    for iteration in 1..=iterations {
+        let mut gradient = [0.; FEATURES];
+        for sample in samples {
+            let p = predict(&weights, sample, k);
+            let d = 2. * (p - sample.result) * p * (1. - p) * k;
+            for (g, f) in gradient.iter_mut().zip(sample.features) {
+                *g += d * f;
+            }
+        }
+
+        for i in 0..FEATURES {
+            let g = gradient[i] / samples.len().max(1) as f32;
+            m[i] = BETA_1 * m[i] + (1. - BETA_1) * g;
+            v[i] = BETA_2 * v[i] + (1. - BETA_2) * g * g;
+            let m_hat = m[i] / (1. - BETA_1.powi(iteration as i32));
+            let v_hat = v[i] / (1. - BETA_2.powi(iteration as i32));
+            weights[i] -= LEARNING_RATE * m_hat / (v_hat.sqrt() + EPSILON);
+        }
+
+        if iteration % 100 == 0 {
+            report(iteration, get_error(&weights, samples, k));
+        }
+    }
+    return Evaluator {weights};
+}
@@ -0,0 +1,47 @@
+use std::io::Cursor;
+
+use bevy::{app::AppExit, prelude::*};
+use descend::{
+    board::{Board, Inventory, LineCount},
+    bot::{Computer, Player, Players},
+    game::{Game, Position, Ruleset},
+    headless::{headless_app, Script},
+    tile::{Color, Face, Selected}
+};
+
+/// Plays `script` on an empty board until the app exits.
+fn run(script: &'static str) -> App {
+    let players = Players {red: Player::Human, black: Player::Human};
+    let mut app = headless_app(Game::new(Ruleset::default()), players, Computer::default(), Script::new(Cursor::new(script)));
+    for _ in 0..1000 {
+        app.update();
+        if !app.world.resource::<Events<AppExit>>().is_empty() {
+            return app;
+        }
+    }
+    panic!("the script did not finish");
+}
+
+#[test]
+fn script_plays_navigates_and_refuses_an_illegal_move() {
+    let mut app = run("Kd3\npress P\n# the Wild is in the middle\nKd4\n");
+
+    let game = app.world.query::<&Game>().single(&app.world).clone();
+    let moves: Vec<String> = game.get_history().iter().skip(1).map(|(mv, ..)| mv.unwrap().to_string()).collect();
+    assert_eq!(moves, ["Kd3"]);
+    assert_eq!(game.get_pos(), 0);
+
+    // the board shows the position looked at, not the last one
+    let board = app.world.query::<&Board>().single(&app.world).clone();
+    let inventory = |app: &mut App, color| app.world.query::<(&Inventory, &Color)>()
+        .iter(&app.world)
+        .find(|(_, c)| **c == color)
+        .unwrap().0.clone();
+    let shown = Position {board: board.clone(), red: inventory(&mut app, Color::Red), black: inventory(&mut app, Color::Black)};
+    assert_eq!(shown.to_string(), game.get_start().to_string());
+    assert_eq!(app.world.resource::<LineCount>().0, board.get_lines());
+
+    // the King picked for the refused move is still selected
+    let selected = app.world.query::<&Selected>().single(&app.world).face;
+    assert_eq!(selected, Some(Face::King));
+}
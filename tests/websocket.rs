@@ -0,0 +1,107 @@
+use std::{
+    net::{TcpListener, TcpStream},
+    sync::{Arc, Mutex},
+    thread,
+    time::Duration
+};
+
+use descend::{
+    game::EndReason,
+    protocol::{ServerMessage, Versioned, PROTOCOL_VERSION},
+    server::{serve_websocket, Server},
+    tile::Color
+};
+use tungstenite::{stream::MaybeTlsStream, Message, WebSocket};
+
+type Socket = WebSocket<MaybeTlsStream<TcpStream>>;
+
+fn start() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    let server = Arc::new(Mutex::new(Server::default()));
+    thread::spawn(move || serve_websocket(server, listener));
+    return format!("ws://{address}");
+}
+
+fn connect(url: &str) -> Socket {
+    let (socket, _) = tungstenite::connect(url).unwrap();
+    if let MaybeTlsStream::Plain(stream) = socket.get_ref() {
+        stream.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+    }
+    return socket;
+}
+
+fn send(socket: &mut Socket, json: &str) {
+    socket.send(Message::Text(json.to_string())).unwrap();
+}
+
+/// The next message from the server, which has to carry the protocol version.
+fn recv(socket: &mut Socket) -> ServerMessage {
+    loop {
+        if let Message::Text(text) = socket.read().unwrap() {
+            let json: serde_json::Value = serde_json::from_str(&text).unwrap();
+            assert_eq!(json["v"], PROTOCOL_VERSION, "{text}");
+            return serde_json::from_value::<Versioned<ServerMessage>>(json).unwrap().message;
+        }
+    }
+}
+
+fn join(url: &str, color: Color) -> Socket {
+    let mut socket = connect(url);
+    send(&mut socket, r#"{"v":1,"type":"join","room":"club"}"#);
+    match recv(&mut socket) {
+        ServerMessage::Joined {room, color: joined, ..} => assert_eq!((room.as_str(), joined), ("club", color)),
+        message => panic!("not joined: {message:?}")
+    }
+    match recv(&mut socket) {
+        ServerMessage::State {moves, to_move, ..} => assert_eq!((moves.len(), to_move), (0, Color::Red)),
+        message => panic!("not a state: {message:?}")
+    }
+    return socket;
+}
+
+fn assert_error(socket: &mut Socket, text: &str) {
+    match recv(socket) {
+        ServerMessage::Error {message} => assert!(message.starts_with(text), "{message}"),
+        message => panic!("not an error: {message:?}")
+    }
+}
+
+#[test]
+fn plays_a_game_over_websocket() {
+    let url = start();
+    let mut red = join(&url, Color::Red);
+    let mut black = join(&url, Color::Black);
+
+    send(&mut red, r#"{"v":2,"type":"move","move":"Kd3"}"#);
+    assert_error(&mut red, "unsupported protocol version 2");
+    send(&mut red, r#"{"v":1,"type":"move","move":"Kd4"}"#);
+    assert_error(&mut red, "illegal move");
+
+    send(&mut red, r#"{"v":1,"type":"move","move":"Kd3"}"#);
+    for socket in [&mut red, &mut black] {
+        match recv(socket) {
+            ServerMessage::State {moves, to_move, ..} => assert_eq!((moves, to_move), (vec!["Kd3".to_string()], Color::Black)),
+            message => panic!("not a state: {message:?}")
+        }
+    }
+
+    send(&mut black, r#"{"v":1,"type":"chat","text":"good luck"}"#);
+    for socket in [&mut red, &mut black] {
+        assert_eq!(recv(socket), ServerMessage::Chat {
+            room: "club".to_string(),
+            from: Some(Color::Black),
+            speaker: "black".to_string(),
+            text: "good luck".to_string()
+        });
+    }
+
+    send(&mut black, r#"{"v":1,"type":"resign"}"#);
+    for socket in [&mut red, &mut black] {
+        assert_eq!(recv(socket), ServerMessage::Result {
+            room: "club".to_string(),
+            winner: Some(Color::Red),
+            reason: EndReason::Resignation
+        });
+    }
+}